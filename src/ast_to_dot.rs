@@ -0,0 +1,137 @@
+use std::fmt::Write;
+use tx3_lang::ast::Program;
+use tx3_lang::ast::TxDef;
+
+use crate::diagram::model::{Party, PartyType, TxGraph};
+
+/// Quotes a string as a DOT id, escaping what would end it early.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Declares a party node, shaped after its kind the way the SVG icons tell
+/// them apart.
+fn party_node(id: &str, party: &Party) -> String {
+    let shape = match party.party_type {
+        PartyType::Party => "shape=ellipse",
+        PartyType::Policy => "shape=hexagon",
+        PartyType::Script => "shape=component",
+        PartyType::Parameter => "shape=parallelogram",
+        PartyType::Unknown => "shape=ellipse, style=dashed",
+    };
+
+    format!("{} [label={}, {}];", id, quote(&party.name), shape)
+}
+
+fn edge(from: &str, to: &str, label: Option<&str>, style: Option<&str>) -> String {
+    let mut attrs = Vec::new();
+
+    if let Some(label) = label {
+        attrs.push(format!("label={}", quote(label)));
+    }
+
+    if let Some(style) = style {
+        attrs.push(format!("style={}", style));
+    }
+
+    match attrs.is_empty() {
+        true => format!("{} -> {};", from, to),
+        false => format!("{} -> {} [{}];", from, to, attrs.join(", ")),
+    }
+}
+
+/// Writes the nodes and edges of a tx, with every node id prefixed so that
+/// several txs can share a graph.
+fn write_tx(out: &mut String, ast: &Program, tx: &TxDef, prefix: &str, indent: &str) {
+    let graph = TxGraph::from_tx(ast, tx);
+    let tx_id = format!("{}tx", prefix);
+
+    let mut lines = vec![format!(
+        "{} [label={}, shape=box, style=bold];",
+        tx_id,
+        quote(&graph.name)
+    )];
+
+    for (i, party) in graph.input_parties.iter().enumerate() {
+        lines.push(party_node(&format!("{}in_party{}", prefix, i), party));
+    }
+
+    for (i, party) in graph.output_parties.iter().enumerate() {
+        lines.push(party_node(&format!("{}out_party{}", prefix, i), party));
+    }
+
+    for (i, input) in graph.inputs.iter().enumerate() {
+        let id = format!("{}input{}", prefix, i);
+        lines.push(format!(
+            "{} [label={}, shape=box, style=rounded];",
+            id,
+            quote(&input.name)
+        ));
+        lines.push(edge(&id, &tx_id, None, None));
+    }
+
+    for (input, party) in graph.input_edges() {
+        let from = format!("{}in_party{}", prefix, party);
+        let to = format!("{}input{}", prefix, input);
+        lines.push(edge(&from, &to, None, Some("dashed")));
+    }
+
+    for (i, output) in graph.outputs.iter().enumerate() {
+        let id = format!("{}output{}", prefix, i);
+        lines.push(format!(
+            "{} [label={}, shape=box, style=rounded];",
+            id,
+            quote(&output.name)
+        ));
+        lines.push(edge(&tx_id, &id, output.amount.as_deref(), None));
+    }
+
+    for (output, party) in graph.output_edges() {
+        let from = format!("{}output{}", prefix, output);
+        let to = format!("{}out_party{}", prefix, party);
+        lines.push(edge(&from, &to, None, Some("dashed")));
+    }
+
+    for (i, change) in graph.supply_changes.iter().enumerate() {
+        let id = format!("{}policy{}", prefix, i);
+        lines.push(party_node(&id, &change.policy));
+
+        // minted assets flow into the tx, burnt ones out of it
+        let (verb, from, to) = match change.is_burn {
+            false => ("mint", &id, &tx_id),
+            true => ("burn", &tx_id, &id),
+        };
+
+        let label = format!("{} {}", verb, change.amount);
+        lines.push(edge(from, to, Some(&label), Some("dotted")));
+    }
+
+    for line in lines {
+        writeln!(out, "{}{}", indent, line).unwrap();
+    }
+}
+
+/// Renders a tx as a Graphviz digraph: parties on the left send inputs into
+/// the tx, which sends outputs to the parties on the right.
+pub fn tx_to_dot(ast: &Program, tx: &TxDef) -> String {
+    let mut out = String::from("digraph {\n    rankdir=LR;\n");
+    write_tx(&mut out, ast, tx, "", "    ");
+    out.push_str("}\n");
+    out
+}
+
+/// Renders every tx of a program into a single Graphviz digraph, one cluster
+/// per tx.
+pub fn program_to_dot(ast: &Program) -> String {
+    let mut out = String::from("digraph {\n    rankdir=LR;\n");
+
+    for (i, tx) in ast.txs.iter().enumerate() {
+        writeln!(out, "    subgraph cluster_tx{} {{", i).unwrap();
+        writeln!(out, "        label={};", quote(&tx.name.value)).unwrap();
+        write_tx(&mut out, ast, tx, &format!("tx{}_", i), "        ");
+        writeln!(out, "    }}").unwrap();
+    }
+
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,121 @@
+use std::fmt::Write;
+use tx3_lang::ast::Program;
+use tx3_lang::ast::TxDef;
+
+use crate::diagram::model::{Party, PartyType, TxGraph};
+
+/// Quotes a label, escaping what Mermaid would otherwise take as syntax.
+fn label(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+/// Declares a party node, shaped after its kind the way the SVG icons tell
+/// them apart.
+fn party_node(id: &str, party: &Party) -> String {
+    let name = label(&party.name);
+
+    match party.party_type {
+        PartyType::Party => format!("{}([{}])", id, name),
+        PartyType::Policy => format!("{}{{{{{}}}}}", id, name),
+        PartyType::Script => format!("{}[[{}]]", id, name),
+        PartyType::Parameter => format!("{}[/{}/]", id, name),
+        PartyType::Unknown => format!("{}({})", id, name),
+    }
+}
+
+/// Writes the nodes and edges of a tx, with every node id prefixed so that
+/// several txs can share a chart.
+fn write_tx(out: &mut String, ast: &Program, tx: &TxDef, prefix: &str, indent: &str) {
+    let graph = TxGraph::from_tx(ast, tx);
+    let tx_id = format!("{}tx", prefix);
+
+    writeln!(out, "{}{}[{}]", indent, tx_id, label(&graph.name)).unwrap();
+
+    for (i, party) in graph.input_parties.iter().enumerate() {
+        let id = format!("{}in_party{}", prefix, i);
+        writeln!(out, "{}{}", indent, party_node(&id, party)).unwrap();
+    }
+
+    for (i, party) in graph.output_parties.iter().enumerate() {
+        let id = format!("{}out_party{}", prefix, i);
+        writeln!(out, "{}{}", indent, party_node(&id, party)).unwrap();
+    }
+
+    for (i, input) in graph.inputs.iter().enumerate() {
+        let id = format!("{}input{}", prefix, i);
+        writeln!(out, "{}{}[{}]", indent, id, label(&input.name)).unwrap();
+        writeln!(out, "{}{} --> {}", indent, id, tx_id).unwrap();
+    }
+
+    for (input, party) in graph.input_edges() {
+        writeln!(
+            out,
+            "{}{}in_party{} --> {}input{}",
+            indent, prefix, party, prefix, input
+        )
+        .unwrap();
+    }
+
+    for (i, output) in graph.outputs.iter().enumerate() {
+        let id = format!("{}output{}", prefix, i);
+        writeln!(out, "{}{}[{}]", indent, id, label(&output.name)).unwrap();
+
+        match &output.amount {
+            Some(amount) => {
+                writeln!(out, "{}{} -->|{}| {}", indent, tx_id, label(amount), id).unwrap()
+            }
+            None => writeln!(out, "{}{} --> {}", indent, tx_id, id).unwrap(),
+        }
+    }
+
+    for (output, party) in graph.output_edges() {
+        writeln!(
+            out,
+            "{}{}output{} --> {}out_party{}",
+            indent, prefix, output, prefix, party
+        )
+        .unwrap();
+    }
+
+    for (i, change) in graph.supply_changes.iter().enumerate() {
+        let id = format!("{}policy{}", prefix, i);
+        writeln!(out, "{}{}", indent, party_node(&id, &change.policy)).unwrap();
+
+        // minted assets flow into the tx, burnt ones out of it
+        let (verb, from, to) = match change.is_burn {
+            false => ("mint", &id, &tx_id),
+            true => ("burn", &tx_id, &id),
+        };
+
+        let text = format!("{} {}", verb, change.amount);
+        writeln!(out, "{}{} -.->|{}| {}", indent, from, label(&text), to).unwrap();
+    }
+}
+
+/// Renders a tx as a Mermaid flowchart: parties on the left send inputs into
+/// the tx, which sends outputs to the parties on the right.
+pub fn tx_to_mermaid(ast: &Program, tx: &TxDef) -> String {
+    let mut out = String::from("flowchart LR\n");
+    write_tx(&mut out, ast, tx, "", "    ");
+    out
+}
+
+/// Renders every tx of a program into a single Mermaid flowchart, one
+/// subgraph per tx.
+pub fn program_to_mermaid(ast: &Program) -> String {
+    let mut out = String::from("flowchart LR\n");
+
+    for (i, tx) in ast.txs.iter().enumerate() {
+        writeln!(
+            out,
+            "    subgraph tx{}_group [{}]",
+            i,
+            label(&tx.name.value)
+        )
+        .unwrap();
+        write_tx(&mut out, ast, tx, &format!("tx{}_", i), "        ");
+        writeln!(out, "    end").unwrap();
+    }
+
+    out
+}
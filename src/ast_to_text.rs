@@ -0,0 +1,333 @@
+use std::fmt::Write;
+use tx3_lang::ast::DataExpr;
+use tx3_lang::ast::InputBlockField;
+use tx3_lang::ast::MintBlockField;
+use tx3_lang::ast::OutputBlockField;
+use tx3_lang::ast::PolicyDef;
+use tx3_lang::ast::PolicyField;
+use tx3_lang::ast::PolicyValue;
+use tx3_lang::ast::Program;
+use tx3_lang::ast::TxDef;
+use tx3_lang::ast::Type;
+use tx3_lang::ast::ValidityBlockField;
+
+use crate::diagram::model::{Party, PartyType, TxGraph};
+
+/// Prints a type the way it's written in Tx3 source (eg: `List<Bytes>`).
+pub fn type_to_text(ty: &Type) -> String {
+    match ty {
+        Type::Custom(id) => id.value.clone(),
+        Type::List(inner) => format!("List<{}>", type_to_text(inner)),
+        Type::Map(key, value) => format!("Map<{}, {}>", type_to_text(key), type_to_text(value)),
+        // primitives are named the same in Rust and Tx3
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn data_expr_to_text(expr: &DataExpr) -> String {
+    match expr {
+        DataExpr::None => "None".to_string(),
+        DataExpr::Unit => "()".to_string(),
+        DataExpr::Number(x) => x.to_string(),
+        DataExpr::Bool(x) => x.to_string(),
+        DataExpr::String(x) => format!("\"{}\"", x.value),
+        DataExpr::HexString(x) => format!("0x{}", x.value),
+        DataExpr::Identifier(x) => x.value.clone(),
+        DataExpr::StructConstructor(x) => format!("{} {{ ... }}", x.r#type.value),
+        DataExpr::ListConstructor(x) => format!(
+            "[{}]",
+            x.elements
+                .iter()
+                .map(data_expr_to_text)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        DataExpr::FnCall(x) => format!(
+            "{}({})",
+            x.callee.value,
+            x.args
+                .iter()
+                .map(data_expr_to_text)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        DataExpr::AnyAssetConstructor(x) => format!(
+            "AnyAsset({}, {}, {})",
+            data_expr_to_text(&x.policy),
+            data_expr_to_text(&x.asset_name),
+            data_expr_to_text(&x.amount)
+        ),
+        DataExpr::AddOp(x) => format!(
+            "{} + {}",
+            data_expr_to_text(&x.lhs),
+            data_expr_to_text(&x.rhs)
+        ),
+        DataExpr::SubOp(x) => format!(
+            "{} - {}",
+            data_expr_to_text(&x.lhs),
+            data_expr_to_text(&x.rhs)
+        ),
+        DataExpr::PropertyOp(x) => format!(
+            "{}.{}",
+            data_expr_to_text(&x.operand),
+            data_expr_to_text(&x.property)
+        ),
+        _ => "...".to_string(),
+    }
+}
+
+/// Formats an amount of lovelace as ADA, eg: `1500000` as `1.5`.
+fn lovelace_to_ada(lovelace: i64) -> String {
+    let sign = if lovelace < 0 { "-" } else { "" };
+    let lovelace = lovelace.unsigned_abs();
+
+    let fraction = format!("{:06}", lovelace % 1_000_000);
+    let fraction = fraction.trim_end_matches('0');
+    let fraction = if fraction.is_empty() { "0" } else { fraction };
+
+    format!("{}{}.{}", sign, lovelace / 1_000_000, fraction)
+}
+
+fn describe_asset_term(ast: &Program, expr: &DataExpr, lines: &mut Vec<String>, sign: &str) {
+    match expr {
+        DataExpr::FnCall(x) if x.callee.value == "Ada" && x.args.len() == 1 => match &x.args[0] {
+            DataExpr::Number(n) => lines.push(format!(
+                "{}{} ADA (`{}` lovelace)",
+                sign,
+                lovelace_to_ada(*n),
+                n
+            )),
+            other => lines.push(format!("{}`{}` lovelace", sign, data_expr_to_text(other))),
+        },
+        DataExpr::FnCall(x) if x.args.len() == 1 => {
+            let asset = ast.assets.iter().find(|a| a.name.value == x.callee.value);
+
+            match asset {
+                Some(asset) => lines.push(format!(
+                    "{}`{}` of `{}` (policy `{}`, asset name `{}`)",
+                    sign,
+                    data_expr_to_text(&x.args[0]),
+                    asset.name.value,
+                    data_expr_to_text(&asset.policy),
+                    data_expr_to_text(&asset.asset_name)
+                )),
+                None => lines.push(format!("{}`{}`", sign, data_expr_to_text(expr))),
+            }
+        }
+        DataExpr::AnyAssetConstructor(x) => lines.push(format!(
+            "{}`{}` of policy `{}`, asset name `{}`",
+            sign,
+            data_expr_to_text(&x.amount),
+            data_expr_to_text(&x.policy),
+            data_expr_to_text(&x.asset_name)
+        )),
+        DataExpr::AddOp(x) => {
+            describe_asset_term(ast, &x.lhs, lines, sign);
+            describe_asset_term(ast, &x.rhs, lines, sign);
+        }
+        DataExpr::SubOp(x) => {
+            describe_asset_term(ast, &x.lhs, lines, sign);
+            let negated = if sign.is_empty() { "minus " } else { "" };
+            describe_asset_term(ast, &x.rhs, lines, negated);
+        }
+        // anything else (eg: an input) is shown as written
+        other => lines.push(format!("{}`{}`", sign, data_expr_to_text(other))),
+    }
+}
+
+fn has_asset_constructor(expr: &DataExpr) -> bool {
+    match expr {
+        DataExpr::FnCall(_) | DataExpr::AnyAssetConstructor(_) => true,
+        DataExpr::AddOp(x) => has_asset_constructor(&x.lhs) || has_asset_constructor(&x.rhs),
+        DataExpr::SubOp(x) => has_asset_constructor(&x.lhs) || has_asset_constructor(&x.rhs),
+        _ => false,
+    }
+}
+
+/// Breaks an asset expression down into a markdown list, one line per term
+/// of a sum, with lovelace amounts shown as ADA and named assets resolved to
+/// their policy and asset name. Returns `None` for expressions that don't
+/// build any asset.
+pub fn asset_expr_to_text(ast: &Program, expr: &DataExpr) -> Option<String> {
+    if !has_asset_constructor(expr) {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    describe_asset_term(ast, expr, &mut lines, "");
+
+    Some(
+        lines
+            .iter()
+            .map(|x| format!("- {}\n", x))
+            .collect::<String>(),
+    )
+}
+
+/// Describes what a policy points to as a markdown list: its assigned hash,
+/// or each of its constructor fields.
+pub fn policy_value_to_text(policy: &PolicyDef) -> String {
+    match &policy.value {
+        PolicyValue::Assign(x) => format!("- hash: `0x{}`\n", x.value),
+        PolicyValue::Constructor(x) => x
+            .fields
+            .iter()
+            .map(|field| match field {
+                PolicyField::Hash(x) => format!("- hash: `{}`\n", data_expr_to_text(x)),
+                PolicyField::Script(x) => format!("- script: `{}`\n", data_expr_to_text(x)),
+                PolicyField::Ref(x) => format!("- ref: `{}`\n", data_expr_to_text(x)),
+            })
+            .collect(),
+    }
+}
+
+fn party_kind(party: &Party) -> &'static str {
+    match party.party_type {
+        PartyType::Unknown => "unknown",
+        PartyType::Party => "party",
+        PartyType::Policy => "policy",
+        PartyType::Script => "script",
+        PartyType::Parameter => "parameter",
+    }
+}
+
+pub fn tx_to_text(ast: &Program, tx: &TxDef) -> String {
+    let graph = TxGraph::from_tx(ast, tx);
+    let TxGraph {
+        input_parties,
+        output_parties,
+        inputs,
+        outputs,
+        ..
+    } = &graph;
+
+    let mut text = String::new();
+
+    writeln!(text, "tx {}", tx.name.value).unwrap();
+
+    // Parties, deduplicated across both sides of the flow
+    let mut parties: Vec<&Party> = input_parties.iter().chain(output_parties.iter()).collect();
+    parties.sort_by_key(|p| p.name.clone());
+    parties.dedup_by(|a, b| a.name == b.name);
+
+    if !parties.is_empty() {
+        writeln!(text, "\nParties:").unwrap();
+        for party in parties {
+            writeln!(text, "  {} ({})", party.name, party_kind(party)).unwrap();
+        }
+    }
+
+    if !inputs.is_empty() {
+        writeln!(text, "\nInputs:").unwrap();
+        for (input, block) in inputs.iter().zip(&tx.inputs) {
+            let from = input.party.as_deref().unwrap_or("?");
+            write!(text, "  {} <- {}", input.name, from).unwrap();
+
+            for field in &block.fields {
+                if let InputBlockField::MinAmount(expr) = field {
+                    write!(text, " [min amount: {}]", data_expr_to_text(expr)).unwrap();
+                }
+            }
+
+            writeln!(text).unwrap();
+        }
+    }
+
+    if !outputs.is_empty() {
+        writeln!(text, "\nOutputs:").unwrap();
+        for (output, block) in outputs.iter().zip(&tx.outputs) {
+            let to = output.party.as_deref().unwrap_or("?");
+            write!(text, "  {} -> {}", output.name, to).unwrap();
+
+            for field in &block.fields {
+                if let OutputBlockField::Amount(expr) = field {
+                    write!(text, " [amount: {}]", data_expr_to_text(expr)).unwrap();
+                }
+            }
+
+            writeln!(text).unwrap();
+        }
+    }
+
+    for (title, blocks) in [("Mints", &tx.mints), ("Burns", &tx.burns)] {
+        if blocks.is_empty() {
+            continue;
+        }
+
+        writeln!(text, "\n{}:", title).unwrap();
+        for block in blocks {
+            for field in &block.fields {
+                if let MintBlockField::Amount(expr) = field {
+                    writeln!(text, "  {}", data_expr_to_text(expr)).unwrap();
+                }
+            }
+        }
+    }
+
+    if let Some(validity) = &tx.validity {
+        writeln!(text, "\nValidity:").unwrap();
+        for field in &validity.fields {
+            match field {
+                ValidityBlockField::SinceSlot(expr) => {
+                    writeln!(text, "  since slot: {}", data_expr_to_text(expr)).unwrap();
+                }
+                ValidityBlockField::UntilSlot(expr) => {
+                    writeln!(text, "  until slot: {}", data_expr_to_text(expr)).unwrap();
+                }
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"party Sender;
+party Receiver;
+
+tx transfer(quantity: Int) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: Receiver,
+        amount: Ada(quantity),
+    }
+    output {
+        to: Sender,
+        amount: source - Ada(quantity),
+    }
+    validity {
+        until_slot: 1000,
+    }
+}
+"#;
+
+    #[test]
+    fn tx_summary_lists_the_flow() {
+        let ast = tx3_lang::parsing::parse_string(TEXT).unwrap();
+
+        let expected = "tx transfer
+
+Parties:
+  Receiver (party)
+  Sender (party)
+
+Inputs:
+  source <- Sender [min amount: Ada(quantity)]
+
+Outputs:
+  output 1 -> Receiver [amount: Ada(quantity)]
+  output 2 -> Sender [amount: source - Ada(quantity)]
+
+Validity:
+  until slot: 1000
+";
+
+        assert_eq!(tx_to_text(&ast, &ast.txs[0]), expected);
+    }
+}
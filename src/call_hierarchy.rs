@@ -0,0 +1,109 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{CallHierarchyIncomingCall, CallHierarchyItem, SymbolKind, Url};
+use tx3_lang::ast::{Identifier, Program, Span, TxDef};
+
+use crate::references::{find_occurrences, resolve_at, Scope};
+use crate::visitor::Role;
+use crate::{span_to_lsp_range, PositionEncoding};
+
+fn make_item(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    name: &Identifier,
+    span: &Span,
+    kind: SymbolKind,
+    detail: &str,
+) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: name.value.clone(),
+        kind,
+        tags: None,
+        detail: Some(detail.to_string()),
+        uri: uri.clone(),
+        range: span_to_lsp_range(rope, encoding, span),
+        selection_range: span_to_lsp_range(rope, encoding, &name.span),
+        data: None,
+    }
+}
+
+/// Resolves the party, policy or asset under the cursor into the item whose
+/// callers are the txs using it.
+pub fn prepare(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    offset: usize,
+) -> Option<CallHierarchyItem> {
+    let (identifier, scope, _) = resolve_at(ast, offset)?;
+
+    if !matches!(scope, Scope::Global) {
+        return None;
+    }
+
+    let name = identifier.value.as_str();
+
+    let parties = ast
+        .parties
+        .iter()
+        .map(|x| (&x.name, &x.span, SymbolKind::OBJECT, "Party"));
+
+    let policies = ast
+        .policies
+        .iter()
+        .map(|x| (&x.name, &x.span, SymbolKind::KEY, "Policy"));
+
+    let assets = ast
+        .assets
+        .iter()
+        .map(|x| (&x.name, &x.span, SymbolKind::CONSTANT, "Asset"));
+
+    parties
+        .chain(policies)
+        .chain(assets)
+        .find(|(x, ..)| x.value == name)
+        .map(|(name, span, kind, detail)| make_item(rope, encoding, uri, name, span, kind, detail))
+}
+
+/// Lists the txs referencing `name`, along with the ranges of every use
+/// within each of them.
+pub fn incoming_calls(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    name: &str,
+) -> Vec<CallHierarchyIncomingCall> {
+    let mut calls: Vec<(&TxDef, Vec<&Span>)> = Vec::new();
+
+    for occurrence in find_occurrences(ast, name, Scope::Global) {
+        let (Role::Reference, Some(tx)) = (occurrence.role, occurrence.tx) else {
+            continue;
+        };
+
+        match calls.iter_mut().find(|(x, _)| std::ptr::eq(*x, tx)) {
+            Some((_, spans)) => spans.push(&occurrence.identifier.span),
+            None => calls.push((tx, vec![&occurrence.identifier.span])),
+        }
+    }
+
+    calls
+        .into_iter()
+        .map(|(tx, spans)| CallHierarchyIncomingCall {
+            from: make_item(
+                rope,
+                encoding,
+                uri,
+                &tx.name,
+                &tx.span,
+                SymbolKind::METHOD,
+                "Tx",
+            ),
+            from_ranges: spans
+                .into_iter()
+                .map(|span| span_to_lsp_range(rope, encoding, span))
+                .collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag};
+use tx3_lang::ast::{DataExpr, Identifier, Program, Span};
+
+use crate::references::is_declared;
+use crate::visitor::{collect_occurrences, collect_property_ops, Role};
+use crate::{
+    panic_message, span_to_lsp_range, with_code, PositionEncoding, CODE_LOWERING_ERROR,
+    CODE_UNDEFINED_PROPERTY_BASE, CODE_UNUSED_DECLARATION,
+};
+
+/// Flags property accesses (eg: `source.amount`) whose base isn't an input,
+/// output or any other name visible from the enclosing tx.
+pub fn undefined_property_bases(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    collect_property_ops(ast)
+        .into_iter()
+        .filter_map(|(op, tx)| match op.operand.as_ref() {
+            DataExpr::Identifier(base) if !is_declared(ast, tx, &base.value) => {
+                let diagnostic = Diagnostic {
+                    range: span_to_lsp_range(rope, encoding, &base.span),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("tx3".to_string()),
+                    message: format!(
+                        "`{}` is not an input or output of tx `{}`",
+                        base.value, tx.name.value
+                    ),
+                    ..Default::default()
+                };
+
+                Some(with_code(diagnostic, CODE_UNDEFINED_PROPERTY_BASE))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Lowers every tx of an analyzed program, reporting the ones that fail on
+/// their declaration. A failing tx, even one that panics the compiler, doesn't
+/// prevent the rest from being checked.
+pub fn lowering_errors(ast: &Program, rope: &Rope, encoding: PositionEncoding) -> Vec<Diagnostic> {
+    ast.txs
+        .iter()
+        .filter_map(|tx| {
+            let lowered = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                tx3_lang::lowering::lower(ast, &tx.name.value)
+            }));
+
+            let err = match lowered {
+                Ok(Ok(_)) => return None,
+                Ok(Err(err)) => err.to_string(),
+                Err(panic) => format!("lowering panicked: {}", panic_message(panic.as_ref())),
+            };
+
+            let diagnostic = Diagnostic {
+                range: span_to_lsp_range(rope, encoding, &tx.span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("tx3".to_string()),
+                message: format!("tx `{}` can't be lowered: {}", tx.name.value, err),
+                ..Default::default()
+            };
+
+            Some(with_code(diagnostic, CODE_LOWERING_ERROR))
+        })
+        .collect()
+}
+
+/// Finds the parties, policies, types and assets that are never referenced,
+/// along with their kind. Uses within a tx that resolve to a parameter of the
+/// same name instead don't count.
+pub fn find_unused_declarations(ast: &Program) -> Vec<(&Identifier, &Span, &'static str)> {
+    let used: HashSet<&str> = collect_occurrences(ast)
+        .into_iter()
+        .filter(|x| x.role == Role::Reference)
+        .filter(|x| {
+            x.tx.is_none_or(|tx| {
+                !tx.parameters
+                    .parameters
+                    .iter()
+                    .any(|param| param.name.value == x.identifier.value)
+            })
+        })
+        .map(|x| x.identifier.value.as_str())
+        .collect();
+
+    let parties = ast.parties.iter().map(|x| (&x.name, &x.span, "party"));
+    let policies = ast.policies.iter().map(|x| (&x.name, &x.span, "policy"));
+    let types = ast.types.iter().map(|x| (&x.name, &x.span, "type"));
+    let assets = ast.assets.iter().map(|x| (&x.name, &x.span, "asset"));
+
+    parties
+        .chain(policies)
+        .chain(types)
+        .chain(assets)
+        .filter(|(name, ..)| !used.contains(name.value.as_str()))
+        .collect()
+}
+
+/// Flags the declarations found by `find_unused_declarations`.
+pub fn unused_declarations(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    find_unused_declarations(ast)
+        .into_iter()
+        .map(|(name, span, kind)| {
+            let diagnostic = Diagnostic {
+                range: span_to_lsp_range(rope, encoding, span),
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some("tx3".to_string()),
+                message: format!("{} `{}` is never used", kind, name.value),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Default::default()
+            };
+
+            with_code(diagnostic, CODE_UNUSED_DECLARATION)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn property_accesses_on_undeclared_names_are_flagged() {
+        let text = r#"party Sender;
+
+type Order {
+    amount: Int,
+}
+
+tx pay(order: Order) {
+    input source {
+        from: Sender,
+        min_amount: Ada(order.amount),
+    }
+    output {
+        to: Sender,
+        amount: Ada(sourse.amount),
+    }
+}
+"#;
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+        let rope = Rope::from_str(text);
+
+        let diagnostics = undefined_property_bases(&ast, &rope, PositionEncoding::Utf16);
+
+        let start = text.find("sourse").unwrap();
+        let range = span_to_lsp_range(&rope, PositionEncoding::Utf16, &Span::new(start, start + 6));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, range);
+        assert_eq!(
+            diagnostics[0].message,
+            "`sourse` is not an input or output of tx `pay`"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+use ropey::Rope;
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::Url;
+
+use crate::{Context, Error};
+
+pub struct Args {
+    document_url: String,
+    /// Source to use when the document isn't open.
+    document_text: Option<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            document_text: value.get(1).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        })
+    }
+}
+
+/// Parses and analyzes a document, returning the same diagnostics the server
+/// would publish for it:
+///
+/// ```json
+/// [{ "range": {...}, "severity": 1, "message": "...", "source": "...", "code": "..." }]
+/// ```
+///
+/// Nothing is published nor cached, so the command works for documents that
+/// were never opened and leaves the state of open ones untouched.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let uri = Url::from_str(&args.document_url)?;
+
+    let rope = match (context.get_document(&args.document_url), args.document_text) {
+        (Err(Error::DocumentNotFound(_)), Some(text)) => Rope::from_str(&text),
+        (result, _) => result?,
+    };
+
+    let diagnostics: Vec<Value> = context
+        .diagnose_document(&uri, &rope)
+        .into_iter()
+        .map(|x| {
+            json!({
+                "range": x.range,
+                "severity": x.severity,
+                "message": x.message,
+                "source": x.source,
+                "code": x.code,
+            })
+        })
+        .collect();
+
+    Ok(Some(Value::Array(diagnostics)))
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use tx3_tir::reduce::Apply;
+
+use crate::{Context, Error};
+
+#[derive(Debug)]
+pub struct Args {
+    document_url: String,
+    tx_names: Vec<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            tx_names: value
+                .get(1)
+                .and_then(|v| v.as_array())
+                .and_then(|v| {
+                    v.iter()
+                        .map(|x| x.as_str().map(|s| s.to_owned()))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or(Error::InvalidCommandArgs("tx_names".to_string()))?,
+        })
+    }
+}
+
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let mut program = context.get_document_program(&args.document_url)?;
+
+    tx3_lang::analyzing::analyze(&mut program).ok()?;
+
+    // param name -> (tx name, param type) for every tx that declares it
+    let mut declarations: BTreeMap<String, Vec<(String, Value)>> = BTreeMap::new();
+
+    for tx_name in &args.tx_names {
+        if !program.txs.iter().any(|tx| &tx.name.value == tx_name) {
+            return Err(Error::TxNotFound(tx_name.clone()));
+        }
+
+        let tx = tx3_lang::lowering::lower(&program, tx_name)?;
+
+        let params = serde_json::to_value(tx.params()).unwrap_or_default();
+
+        for (name, ty) in params.as_object().into_iter().flatten() {
+            declarations
+                .entry(name.clone())
+                .or_default()
+                .push((tx_name.clone(), ty.clone()));
+        }
+    }
+
+    let mut parameters = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (name, decls) in declarations {
+        let (_, first_type) = &decls[0];
+
+        if decls.iter().all(|(_, ty)| ty == first_type) {
+            parameters.push(json!({
+                "name": name,
+                "type": first_type,
+                "txs": decls.iter().map(|(tx, _)| tx).collect::<Vec<_>>(),
+            }));
+        } else {
+            conflicts.push(json!({
+                "name": name,
+                "declarations": decls
+                    .iter()
+                    .map(|(tx, ty)| json!({ "tx_name": tx, "type": ty }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+    }
+
+    let out = json!({
+        "parameters": parameters,
+        "conflicts": conflicts,
+    });
+
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tower_lsp::{lsp_types::Url, LspService};
+
+    use super::*;
+
+    const TEXT: &str = r#"party Sender;
+party Receiver;
+
+tx pay(quantity: Int, note: Bytes) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: Receiver,
+        amount: Ada(quantity),
+        datum: note,
+    }
+}
+
+tx refund(quantity: Int, note: Int) {
+    input source {
+        from: Receiver,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: Sender,
+        amount: Ada(quantity),
+        datum: note,
+    }
+}
+"#;
+
+    async fn collect(tx_names: Value) -> Result<Option<Value>, Error> {
+        let (service, _) = LspService::new(Context::new_for_client);
+        let uri = Url::parse("file:///test.tx3").unwrap();
+        service
+            .inner()
+            .documents
+            .insert(uri.clone(), Rope::from_str(TEXT));
+
+        run(service.inner(), vec![json!(uri.to_string()), tx_names]).await
+    }
+
+    #[tokio::test]
+    async fn shared_params_are_merged_and_clashing_ones_reported() {
+        let out = collect(json!(["pay", "refund"])).await.unwrap().unwrap();
+
+        let quantity = out["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|x| x["name"] == "quantity")
+            .unwrap();
+        assert_eq!(quantity["txs"], json!(["pay", "refund"]));
+
+        let conflicts = out["conflicts"].as_array().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0]["name"], "note");
+
+        let declarations = conflicts[0]["declarations"].as_array().unwrap();
+        assert_eq!(declarations[0]["tx_name"], "pay");
+        assert_eq!(declarations[1]["tx_name"], "refund");
+        assert_ne!(declarations[0]["type"], declarations[1]["type"]);
+    }
+
+    #[tokio::test]
+    async fn unknown_txs_are_reported() {
+        let err = collect(json!(["pay", "swap"])).await.unwrap_err();
+
+        assert!(matches!(err, Error::TxNotFound(name) if name == "swap"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::panic::AssertUnwindSafe;
+
+use serde_json::{json, Value};
+
+use crate::{panic_message, Context, Error};
+
+pub struct Args {
+    document_url: String,
+    /// Source to use when the document isn't open.
+    document_text: Option<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            document_text: value.get(1).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        })
+    }
+}
+
+/// Analyzes and lowers every tx of a document, without encoding them:
+///
+/// ```json
+/// [{ "tx_name": "...", "ok": false, "error": "..." }]
+/// ```
+///
+/// Failures are reported per tx, including panics within the compiler, so a
+/// single broken tx doesn't hide the state of the rest. Only a document that
+/// doesn't parse fails the whole command.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let mut program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
+
+    let analysis = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        tx3_lang::analyzing::analyze(&mut program)
+    }));
+
+    // lowering relies on a clean analysis, which is shared by every tx
+    let analysis_error = match analysis {
+        Ok(report) if report.errors.is_empty() => None,
+        Ok(report) => Some(format!(
+            "analysis failed: {}",
+            report
+                .errors
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        )),
+        Err(panic) => Some(format!(
+            "analysis panicked: {}",
+            panic_message(panic.as_ref())
+        )),
+    };
+
+    let results: Vec<Value> = program
+        .txs
+        .iter()
+        .map(|tx| {
+            let error = match &analysis_error {
+                Some(err) => Some(err.clone()),
+                None => {
+                    let lowered = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        tx3_lang::lowering::lower(&program, &tx.name.value)
+                    }));
+
+                    match lowered {
+                        Ok(Ok(_)) => None,
+                        Ok(Err(err)) => Some(err.to_string()),
+                        Err(panic) => Some(format!(
+                            "lowering panicked: {}",
+                            panic_message(panic.as_ref())
+                        )),
+                    }
+                }
+            };
+
+            json!({
+                "tx_name": tx.name.value,
+                "ok": error.is_none(),
+                "error": error,
+            })
+        })
+        .collect();
+
+    Ok(Some(Value::Array(results)))
+}
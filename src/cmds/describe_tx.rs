@@ -0,0 +1,45 @@
+use serde_json::Value;
+
+use crate::{ast_to_text::tx_to_text, Context, Error};
+
+#[derive(Debug)]
+pub struct Args {
+    document_url: String,
+    tx_name: String,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            tx_name: value
+                .get(1)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("tx_name".to_string()))?,
+        })
+    }
+}
+
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let program = context.get_document_program(&args.document_url)?;
+
+    let tx = program
+        .txs
+        .iter()
+        .find(|tx| tx.name.value == args.tx_name)
+        .ok_or(Error::TxNotFound(args.tx_name))?;
+
+    Ok(Some(Value::String(tx_to_text(&program, tx))))
+}
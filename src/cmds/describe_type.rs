@@ -0,0 +1,45 @@
+use serde_json::Value;
+
+use crate::{schema::describe_type, Context, Error};
+
+#[derive(Debug)]
+pub struct Args {
+    document_url: String,
+    type_name: String,
+    document_text: Option<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            type_name: value
+                .get(1)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("type_name".to_string()))?,
+            document_text: value.get(2).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        })
+    }
+}
+
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
+
+    let descriptor =
+        describe_type(&program, &args.type_name).ok_or(Error::TypeNotFound(args.type_name))?;
+
+    Ok(Some(descriptor))
+}
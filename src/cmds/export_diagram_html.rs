@@ -0,0 +1,62 @@
+use crate::{ast_to_svg::tx_to_svg, Context, Error};
+use serde_json::Value;
+
+use super::generate_diagram::Args;
+
+/// Renders the diagrams of a document (or a single tx) into a self-contained
+/// HTML page, one section per tx. Returns the page as a string.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+    let theme = args.theme.unwrap_or_else(|| context.config().diagram_theme);
+
+    let mut program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
+
+    // same as generate-diagram, a document that doesn't fully analyze still
+    // renders
+    let _ = tx3_lang::analyzing::analyze(&mut program);
+
+    let txs: Vec<_> = match args.tx_name {
+        Some(tx_name) => vec![program
+            .txs
+            .iter()
+            .find(|tx| tx.name.value == tx_name)
+            .ok_or(Error::TxNotFound(tx_name))?],
+        None => program.txs.iter().collect(),
+    };
+
+    let mut sections = String::new();
+
+    for tx in txs {
+        sections.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n{}\n</section>\n",
+            tx.name.value,
+            tx_to_svg(&program, tx, &theme)
+        ));
+    }
+
+    let background = &theme.background;
+    let color = &theme.text;
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>tx3 diagrams</title>
+<style>
+body {{ background: {background}; color: {color}; font-family: sans-serif; margin: 2em; }}
+section {{ margin-bottom: 3em; }}
+</style>
+</head>
+<body>
+{sections}</body>
+</html>
+"#
+    );
+
+    Ok(Some(Value::String(html)))
+}
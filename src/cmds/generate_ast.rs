@@ -12,7 +12,7 @@ impl TryFrom<Vec<Value>> for Args {
     fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
         Ok(Args {
             document_url: value
-                .get(0)
+                .first()
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_owned())
                 .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
@@ -28,7 +28,9 @@ pub async fn run(
 
     let mut program = context.get_document_program(&args.document_url)?;
 
-    tx3_lang::analyzing::analyze(&mut program).ok().unwrap();
+    // semantic errors are reported as diagnostics; the AST is still useful
+    // for a document that doesn't fully analyze
+    let _ = tx3_lang::analyzing::analyze(&mut program);
 
     let out = json!({
         "ast": program,
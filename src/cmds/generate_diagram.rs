@@ -1,8 +1,16 @@
-use crate::{ast_to_svg::tx_to_svg, Context, Error};
+use crate::{
+    ast_to_svg::{tx_to_svg, DiagramTheme},
+    Context, Error,
+};
 use serde_json::{json, Value};
 
 pub struct Args {
-    document_url: String,
+    pub(super) document_url: String,
+    pub(super) tx_name: Option<String>,
+    /// Overrides the theme from the initialization options.
+    pub(super) theme: Option<DiagramTheme>,
+    /// Source to use when the document isn't open.
+    pub(super) document_text: Option<String>,
 }
 
 impl TryFrom<Vec<Value>> for Args {
@@ -11,10 +19,20 @@ impl TryFrom<Vec<Value>> for Args {
     fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
         Ok(Args {
             document_url: value
-                .get(0)
+                .first()
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_owned())
                 .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            tx_name: value.get(1).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+            theme: value
+                .get(2)
+                .filter(|v| !v.is_null())
+                .map(|v| {
+                    serde_json::from_value(v.clone())
+                        .map_err(|_| Error::InvalidCommandArgs("theme".to_string()))
+                })
+                .transpose()?,
+            document_text: value.get(3).and_then(|v| v.as_str()).map(|s| s.to_owned()),
         })
     }
 }
@@ -24,16 +42,33 @@ pub async fn run(
     args: impl TryInto<Args, Error = Error>,
 ) -> Result<Option<Value>, Error> {
     let args: Args = args.try_into()?;
+    let theme = args.theme.unwrap_or_else(|| context.config().diagram_theme);
 
-    let mut program = context.get_document_program(&args.document_url)?;
+    let mut program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
 
-    tx3_lang::analyzing::analyze(&mut program).ok().unwrap();
+    // semantic errors are reported as diagnostics; the diagram is still useful
+    // for a document that doesn't fully analyze
+    let _ = tx3_lang::analyzing::analyze(&mut program);
+
+    if let Some(tx_name) = args.tx_name {
+        let tx = program
+            .txs
+            .iter()
+            .find(|tx| tx.name.value == tx_name)
+            .ok_or(Error::TxNotFound(tx_name))?;
+
+        return Ok(Some(json!({
+            "tx_name": tx.name.value,
+            "svg": tx_to_svg(&program, tx, &theme),
+        })));
+    }
 
     let tx_svgs: Vec<Value> = program
         .txs
         .iter()
         .map(|tx| {
-            let svg = tx_to_svg(&program, tx);
+            let svg = tx_to_svg(&program, tx, &theme);
             json!({
                 "tx_name": tx.name.value,
                 "svg": svg
@@ -0,0 +1,88 @@
+use serde_json::{json, Value};
+
+use crate::{ast_to_svg::tx_to_svg, Context, Error};
+
+use super::generate_diagram::Args;
+
+// the diagram viewBox is tiny, so we rasterize at a higher resolution
+#[cfg(feature = "png")]
+const SCALE: f32 = 8.0;
+
+#[cfg(feature = "png")]
+fn svg_to_png(svg: &str) -> Result<String, Error> {
+    use base64::Engine as _;
+    use resvg::{tiny_skia, usvg};
+
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|e| Error::DiagramRenderError(e.to_string()))?;
+
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(SCALE)
+        .ok_or_else(|| Error::DiagramRenderError("invalid diagram size".to_string()))?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| Error::DiagramRenderError("invalid diagram size".to_string()))?;
+
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(SCALE, SCALE),
+        &mut pixmap.as_mut(),
+    );
+
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| Error::DiagramRenderError(e.to_string()))?;
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(png))
+}
+
+#[cfg(not(feature = "png"))]
+fn svg_to_png(_svg: &str) -> Result<String, Error> {
+    Err(Error::FeatureNotEnabled("png".to_string()))
+}
+
+/// Same as `generate-diagram`, with each diagram rasterized into a
+/// base64-encoded PNG.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+    let theme = args.theme.unwrap_or_else(|| context.config().diagram_theme);
+
+    let mut program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
+
+    // same as generate-diagram, a document that doesn't fully analyze still
+    // renders
+    let _ = tx3_lang::analyzing::analyze(&mut program);
+
+    if let Some(tx_name) = args.tx_name {
+        let tx = program
+            .txs
+            .iter()
+            .find(|tx| tx.name.value == tx_name)
+            .ok_or(Error::TxNotFound(tx_name))?;
+
+        return Ok(Some(json!({
+            "tx_name": tx.name.value,
+            "png": svg_to_png(&tx_to_svg(&program, tx, &theme))?,
+        })));
+    }
+
+    let tx_pngs = program
+        .txs
+        .iter()
+        .map(|tx| {
+            let png = svg_to_png(&tx_to_svg(&program, tx, &theme))?;
+            Ok(json!({
+                "tx_name": tx.name.value,
+                "png": png
+            }))
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+
+    Ok(Some(Value::Array(tx_pngs)))
+}
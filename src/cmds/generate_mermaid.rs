@@ -0,0 +1,56 @@
+use serde_json::Value;
+
+use crate::{
+    ast_to_mermaid::{program_to_mermaid, tx_to_mermaid},
+    Context, Error,
+};
+
+pub struct Args {
+    document_url: String,
+    /// Renders every tx when missing.
+    tx_name: Option<String>,
+    /// Source to use when the document isn't open.
+    document_text: Option<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            tx_name: value.get(1).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+            document_text: value.get(2).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        })
+    }
+}
+
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let mut program =
+        context.get_document_program_or_text(&args.document_url, args.document_text.as_deref())?;
+
+    // same as the SVG diagrams, a document that doesn't fully analyze is
+    // still worth charting
+    let _ = tx3_lang::analyzing::analyze(&mut program);
+
+    let Some(tx_name) = args.tx_name else {
+        return Ok(Some(Value::String(program_to_mermaid(&program))));
+    };
+
+    let tx = program
+        .txs
+        .iter()
+        .find(|tx| tx.name.value == tx_name)
+        .ok_or(Error::TxNotFound(tx_name))?;
+
+    Ok(Some(Value::String(tx_to_mermaid(&program, tx))))
+}
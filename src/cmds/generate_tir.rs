@@ -1,11 +1,26 @@
+use crate::{Context, Error};
 use serde_json::{json, Value};
+use tx3_tir::encoding::{TirVersion, MIN_SUPPORTED_VERSION};
 use tx3_tir::reduce::Apply;
-use crate::{Context, Error};
+
+/// A warning for clients when the TIR was encoded with a version older than
+/// the minimum one the tooling supports, usually a sign of mismatched
+/// toolchains.
+pub(super) fn version_warning(version: TirVersion) -> Option<String> {
+    (version < MIN_SUPPORTED_VERSION).then(|| {
+        format!(
+            "TIR version {:?} is older than the minimum supported version {:?}",
+            version, MIN_SUPPORTED_VERSION
+        )
+    })
+}
 
 #[derive(Debug)]
 pub struct Args {
-    document_url: String,
-    tx_name: String,
+    pub(super) document_url: String,
+    pub(super) tx_name: String,
+    /// Source to use when the document isn't open.
+    pub(super) document_text: Option<String>,
 }
 
 impl TryFrom<Vec<Value>> for Args {
@@ -14,7 +29,7 @@ impl TryFrom<Vec<Value>> for Args {
     fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
         Ok(Args {
             document_url: value
-                .get(0)
+                .first()
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_owned())
                 .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
@@ -23,29 +38,64 @@ impl TryFrom<Vec<Value>> for Args {
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_owned())
                 .ok_or(Error::InvalidCommandArgs("tx_name".to_string()))?,
+            document_text: value.get(2).and_then(|v| v.as_str()).map(|s| s.to_owned()),
         })
     }
 }
 
+/// Analyzes the document and lowers the requested tx into its TIR. The
+/// document is parsed from `document_text` if it isn't open.
+pub(super) fn lower_tx(
+    context: &Context,
+    document_url: &str,
+    document_text: Option<&str>,
+    tx_name: &str,
+) -> Result<tx3_tir::model::v1beta0::Tx, Error> {
+    let mut program = context.get_document_program_or_text(document_url, document_text)?;
+
+    tx3_lang::analyzing::analyze(&mut program).ok()?;
+
+    if !program.txs.iter().any(|tx| tx.name.value == tx_name) {
+        let available: Vec<&str> = program
+            .txs
+            .iter()
+            .map(|tx| tx.name.value.as_str())
+            .collect();
+
+        return Err(Error::TxNotFound(format!(
+            "{} (available: {})",
+            tx_name,
+            available.join(", ")
+        )));
+    }
+
+    Ok(tx3_lang::lowering::lower(&program, tx_name)?)
+}
+
 pub async fn run(
     context: &Context,
     args: impl TryInto<Args, Error = Error>,
 ) -> Result<Option<Value>, Error> {
     let args: Args = args.try_into()?;
 
-    let mut program = context.get_document_program(&args.document_url)?;
-
-    tx3_lang::analyzing::analyze(&mut program).ok().unwrap();
-
-    let tx = tx3_lang::lowering::lower(&program, &args.tx_name).unwrap();
+    let tx = lower_tx(
+        context,
+        &args.document_url,
+        args.document_text.as_deref(),
+        &args.tx_name,
+    )?;
 
     let tir = tx3_tir::encoding::to_bytes(&tx);
 
-    let out = json!({
+    let mut out = json!({
         "tir": hex::encode(&tir.0),
         "version": tir.1,
         "parameters": tx.params(),
     });
 
+    if let Some(warning) = version_warning(tir.1) {
+        out["warning"] = json!(warning);
+    }
+
     Ok(Some(out))
 }
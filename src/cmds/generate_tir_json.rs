@@ -0,0 +1,41 @@
+use crate::{Context, Error};
+use serde_json::{json, Value};
+use tx3_tir::reduce::Apply;
+
+use super::generate_tir::{lower_tx, version_warning, Args};
+
+/// Same as `generate-tir`, but returns the TIR as a JSON tree instead of
+/// hex-encoded bytes:
+///
+/// ```json
+/// { "tir": { ... }, "version": "...", "parameters": { "name": "Type" } }
+/// ```
+///
+/// A `warning` is added when the version isn't the supported one.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let tx = lower_tx(
+        context,
+        &args.document_url,
+        args.document_text.as_deref(),
+        &args.tx_name,
+    )?;
+
+    let (_, version) = tx3_tir::encoding::to_bytes(&tx);
+
+    let mut out = json!({
+        "tir": tx,
+        "version": version,
+        "parameters": tx.params(),
+    });
+
+    if let Some(warning) = version_warning(version) {
+        out["warning"] = json!(warning);
+    }
+
+    Ok(Some(out))
+}
@@ -0,0 +1,59 @@
+use serde_json::{json, Value};
+
+use crate::{ast_to_text::type_to_text, Context, Error};
+
+pub struct Args {
+    document_url: String,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+        })
+    }
+}
+
+/// Lists the txs of a document. A document that doesn't parse is an error
+/// rather than an empty list, so clients can tell both cases apart.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let program = context.get_document_program(&args.document_url)?;
+
+    let txs: Vec<Value> = program
+        .txs
+        .iter()
+        .map(|tx| {
+            let parameters: Vec<Value> = tx
+                .parameters
+                .parameters
+                .iter()
+                .map(|param| {
+                    json!({
+                        "name": param.name.value,
+                        "type": type_to_text(&param.r#type),
+                    })
+                })
+                .collect();
+
+            json!({
+                "name": tx.name.value,
+                "parameters": parameters,
+                "input_count": tx.inputs.len(),
+                "output_count": tx.outputs.len(),
+            })
+        })
+        .collect();
+
+    Ok(Some(Value::Array(txs)))
+}
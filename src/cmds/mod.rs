@@ -4,18 +4,164 @@ use tower_lsp::lsp_types::ExecuteCommandParams;
 
 use crate::{Context, Error};
 
+mod analyze;
+mod collect_params;
+mod compile_all;
+mod describe_tx;
+mod describe_type;
+mod export_diagram_html;
 mod generate_ast;
 mod generate_diagram;
+mod generate_diagram_png;
+mod generate_dot;
+mod generate_mermaid;
 mod generate_tir;
+mod generate_tir_json;
+mod list_txs;
+mod resolve_params;
+mod validate_args;
+
+pub const GENERATE_TIR: &str = "generate-tir";
+pub const GENERATE_TIR_JSON: &str = "generate-tir-json";
+pub const GENERATE_AST: &str = "generate-ast";
+pub const GENERATE_DIAGRAM: &str = "generate-diagram";
+pub const GENERATE_DIAGRAM_PNG: &str = "generate-diagram-png";
+pub const GENERATE_MERMAID: &str = "generate-mermaid";
+pub const GENERATE_DOT: &str = "generate-dot";
+pub const EXPORT_DIAGRAM_HTML: &str = "export-diagram-html";
+pub const DESCRIBE_TX: &str = "describe-tx";
+pub const DESCRIBE_TYPE: &str = "describe-type";
+pub const COLLECT_PARAMS: &str = "collect-params";
+pub const RESOLVE_PARAMS: &str = "resolve-params";
+pub const LIST_TXS: &str = "list-txs";
+pub const VALIDATE_ARGS: &str = "validate-args";
+pub const COMPILE_ALL: &str = "compile-all";
+pub const ANALYZE: &str = "analyze";
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Every command supported by `handle_command`, advertised to clients.
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: GENERATE_TIR,
+        description: "Lower a tx into its hex-encoded TIR bytes",
+    },
+    CommandInfo {
+        name: GENERATE_TIR_JSON,
+        description: "Lower a tx into its TIR as a JSON tree",
+    },
+    CommandInfo {
+        name: GENERATE_AST,
+        description: "Return the analyzed AST of a document as JSON",
+    },
+    CommandInfo {
+        name: GENERATE_DIAGRAM,
+        description: "Render an SVG diagram for each tx in a document",
+    },
+    // only advertised when it can actually render
+    #[cfg(feature = "png")]
+    CommandInfo {
+        name: GENERATE_DIAGRAM_PNG,
+        description: "Render a base64-encoded PNG diagram for each tx in a document",
+    },
+    CommandInfo {
+        name: GENERATE_MERMAID,
+        description: "Render a tx, or every tx in a document, as a Mermaid flowchart",
+    },
+    CommandInfo {
+        name: GENERATE_DOT,
+        description: "Render a tx, or every tx in a document, as a Graphviz DOT graph",
+    },
+    CommandInfo {
+        name: EXPORT_DIAGRAM_HTML,
+        description: "Render the tx diagrams of a document as a standalone HTML page",
+    },
+    CommandInfo {
+        name: DESCRIBE_TX,
+        description: "Summarize a tx as plain text",
+    },
+    CommandInfo {
+        name: DESCRIBE_TYPE,
+        description: "Describe the cases and fields of a type as JSON",
+    },
+    CommandInfo {
+        name: COLLECT_PARAMS,
+        description: "Merge the parameters required by a set of txs",
+    },
+    CommandInfo {
+        name: RESOLVE_PARAMS,
+        description: "List the parameters of a tx along with their types",
+    },
+    CommandInfo {
+        name: LIST_TXS,
+        description: "List the txs of a document with their parameters",
+    },
+    CommandInfo {
+        name: VALIDATE_ARGS,
+        description: "Check argument values against the parameters of a tx",
+    },
+    CommandInfo {
+        name: COMPILE_ALL,
+        description: "Compile every tx of a document, reporting the ones that fail",
+    },
+    CommandInfo {
+        name: ANALYZE,
+        description: "Return the diagnostics of a document as JSON",
+    },
+];
 
 pub async fn handle_command(
     context: &Context,
     params: ExecuteCommandParams,
 ) -> Result<Option<Value>, Error> {
     match params.command.as_str() {
-        "generate-tir" => generate_tir::run(context, params.arguments).await,
-        "generate-ast" => generate_ast::run(context, params.arguments).await,
-        "generate-diagram" => generate_diagram::run(context, params.arguments).await,
+        GENERATE_TIR => generate_tir::run(context, params.arguments).await,
+        GENERATE_TIR_JSON => generate_tir_json::run(context, params.arguments).await,
+        GENERATE_AST => generate_ast::run(context, params.arguments).await,
+        GENERATE_DIAGRAM => generate_diagram::run(context, params.arguments).await,
+        GENERATE_DIAGRAM_PNG => generate_diagram_png::run(context, params.arguments).await,
+        GENERATE_MERMAID => generate_mermaid::run(context, params.arguments).await,
+        GENERATE_DOT => generate_dot::run(context, params.arguments).await,
+        EXPORT_DIAGRAM_HTML => export_diagram_html::run(context, params.arguments).await,
+        DESCRIBE_TX => describe_tx::run(context, params.arguments).await,
+        DESCRIBE_TYPE => describe_type::run(context, params.arguments).await,
+        COLLECT_PARAMS => collect_params::run(context, params.arguments).await,
+        RESOLVE_PARAMS => resolve_params::run(context, params.arguments).await,
+        LIST_TXS => list_txs::run(context, params.arguments).await,
+        VALIDATE_ARGS => validate_args::run(context, params.arguments).await,
+        COMPILE_ALL => compile_all::run(context, params.arguments).await,
+        ANALYZE => analyze::run(context, params.arguments).await,
         _ => Err(Error::InvalidCommand(params.command)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LspService;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn every_listed_command_is_handled() {
+        let (service, _) = LspService::new(Context::new_for_client);
+
+        for cmd in COMMANDS {
+            let params = ExecuteCommandParams {
+                command: cmd.name.to_string(),
+                arguments: vec![],
+                work_done_progress_params: Default::default(),
+            };
+
+            let result = handle_command(service.inner(), params).await;
+
+            assert!(
+                !matches!(result, Err(Error::InvalidCommand(_))),
+                "`{}` isn't handled",
+                cmd.name
+            );
+        }
+    }
+}
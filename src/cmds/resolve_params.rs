@@ -0,0 +1,36 @@
+use crate::{Context, Error};
+use serde_json::{json, Value};
+use tx3_tir::reduce::Apply;
+
+use super::generate_tir::{lower_tx, Args};
+
+/// Returns the parameters of a tx without encoding it, cheap enough to be
+/// called on every edit:
+///
+/// ```json
+/// [{ "name": "...", "type": ... }]
+/// ```
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let tx = lower_tx(
+        context,
+        &args.document_url,
+        args.document_text.as_deref(),
+        &args.tx_name,
+    )?;
+
+    let params = serde_json::to_value(tx.params()).unwrap_or_default();
+
+    let parameters: Vec<Value> = params
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, ty)| json!({ "name": name, "type": ty }))
+        .collect();
+
+    Ok(Some(Value::Array(parameters)))
+}
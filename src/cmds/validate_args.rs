@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+use tx3_tir::model::core::Type;
+use tx3_tir::model::core::UtxoRef;
+use tx3_tir::reduce::{Apply, ArgValue};
+
+use crate::{Context, Error};
+
+use super::generate_tir::lower_tx;
+
+pub struct Args {
+    document_url: String,
+    tx_name: String,
+    values: Map<String, Value>,
+    /// Source to use when the document isn't open.
+    document_text: Option<String>,
+}
+
+impl TryFrom<Vec<Value>> for Args {
+    type Error = Error;
+
+    fn try_from(value: Vec<Value>) -> Result<Self, Self::Error> {
+        Ok(Args {
+            document_url: value
+                .first()
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("document_url".to_string()))?,
+            tx_name: value
+                .get(1)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs("tx_name".to_string()))?,
+            values: value
+                .get(2)
+                .and_then(|v| v.as_object())
+                .cloned()
+                .ok_or(Error::InvalidCommandArgs("args".to_string()))?,
+            document_text: value.get(3).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        })
+    }
+}
+
+fn hex_bytes(value: &Value) -> Option<Vec<u8>> {
+    let text = value.as_str()?;
+    hex::decode(text.strip_prefix("0x").unwrap_or(text)).ok()
+}
+
+fn type_name(ty: &Type) -> &str {
+    match ty {
+        Type::Int => "Int",
+        Type::Bool => "Bool",
+        Type::Bytes => "Bytes",
+        Type::Address => "Address",
+        Type::UtxoRef => "UtxoRef",
+        Type::Custom(name) => name,
+        _ => "a value",
+    }
+}
+
+/// Reads a JSON value as an argument for a parameter of type `ty`, the same
+/// way frontends encode them when submitting. Returns `Ok(None)` for types
+/// without a JSON form of their own (eg: custom ones), which are left for the
+/// tx to check.
+fn to_arg_value(ty: &Type, value: &Value) -> Result<Option<ArgValue>, String> {
+    let arg = match ty {
+        Type::Int => match value {
+            Value::Number(x) => x
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| x.as_u64().map(i128::from)),
+            Value::String(x) => x.parse::<i128>().ok(),
+            _ => None,
+        }
+        .map(ArgValue::Int),
+        Type::Bool => value.as_bool().map(ArgValue::Bool),
+        Type::Bytes => hex_bytes(value).map(ArgValue::Bytes),
+        Type::Address => match value.as_str() {
+            // bech32 addresses are decoded by the tx itself
+            Some(x) if x.starts_with("addr") => Some(ArgValue::String(x.to_string())),
+            _ => hex_bytes(value).map(ArgValue::Address),
+        },
+        Type::UtxoRef => {
+            value
+                .as_str()
+                .and_then(|x| x.split_once('#'))
+                .and_then(|(hash, index)| {
+                    Some(ArgValue::UtxoRef(UtxoRef {
+                        txid: hex::decode(hash).ok()?,
+                        index: index.parse().ok()?,
+                    }))
+                })
+        }
+        _ if value.is_null() => None,
+        _ => return Ok(None),
+    };
+
+    match arg {
+        Some(arg) => Ok(Some(arg)),
+        None => Err(format!("expected {}, got {}", type_name(ty), value)),
+    }
+}
+
+/// Validates argument values for a tx before they're submitted:
+///
+/// ```json
+/// { "valid": false, "errors": { "<param>": "..." } }
+/// ```
+///
+/// Each value is applied to the lowered tx on its own, so that missing
+/// parameters, unknown ones and values the tx rejects are reported, keyed by
+/// parameter name.
+pub async fn run(
+    context: &Context,
+    args: impl TryInto<Args, Error = Error>,
+) -> Result<Option<Value>, Error> {
+    let args: Args = args.try_into()?;
+
+    let tx = lower_tx(
+        context,
+        &args.document_url,
+        args.document_text.as_deref(),
+        &args.tx_name,
+    )?;
+    let params = tx.params();
+
+    let mut errors = Map::new();
+
+    for (name, ty) in &params {
+        let Some(value) = args.values.get(name) else {
+            errors.insert(name.clone(), json!("missing value"));
+            continue;
+        };
+
+        let arg = match to_arg_value(ty, value) {
+            Ok(Some(arg)) => arg,
+            Ok(None) => continue,
+            Err(err) => {
+                errors.insert(name.clone(), json!(err));
+                continue;
+            }
+        };
+
+        let applied = tx
+            .clone()
+            .apply_args(&BTreeMap::from([(name.clone(), arg)]));
+
+        if let Err(err) = applied {
+            errors.insert(name.clone(), json!(err.to_string()));
+        }
+    }
+
+    for name in args.values.keys() {
+        if !params.contains_key(name) {
+            errors.insert(name.clone(), json!("unknown parameter"));
+        }
+    }
+
+    Ok(Some(json!({
+        "valid": errors.is_empty(),
+        "errors": errors,
+    })))
+}
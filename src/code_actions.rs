@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, NumberOrString, Position, Range,
+    TextEdit, Url, WorkspaceEdit,
+};
+use tx3_lang::ast::{
+    CollateralBlockField, DataExpr, Identifier, InputBlockField, MintBlockField, OutputBlockField,
+    Program, Span, StructConstructor, TxDef,
+};
+
+use crate::ast_to_text::type_to_text;
+use crate::checks::find_unused_declarations;
+use crate::inlay_hints::infer_type;
+use crate::references::{is_declared, resolve_constructor};
+use crate::visitor::collect_struct_constructors;
+use crate::{char_index_to_line_col, span_to_lsp_range, PositionEncoding, CODE_UNUSED_DECLARATION};
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+fn end_of_document(rope: &Rope, encoding: PositionEncoding) -> Position {
+    let (line, col) = char_index_to_line_col(rope, encoding, rope.len_chars());
+    Position::new(line as u32, col as u32)
+}
+
+/// Appends `text` as a new declaration at the end of the document.
+fn append_edit(rope: &Rope, encoding: PositionEncoding, text: &str) -> TextEdit {
+    let position = end_of_document(rope, encoding);
+
+    // keep a blank line between the last declaration and the new one
+    let separator = match rope.chars().last() {
+        None => "",
+        Some('\n') => "\n",
+        Some(_) => "\n\n",
+    };
+
+    TextEdit {
+        range: Range::new(position, position),
+        new_text: format!("{}{}", separator, text),
+    }
+}
+
+/// Inserts `text` as a line of its own right after the declaration at `after`,
+/// or at the top of the document when there's none.
+fn insert_after_edit(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    after: Option<&Span>,
+    text: &str,
+) -> TextEdit {
+    let Some(span) = after else {
+        return TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            new_text: format!("{}\n", text),
+        };
+    };
+
+    let (line, _) = char_index_to_line_col(rope, encoding, span.end);
+
+    // the declaration is on the last line, so there's no next line to insert at
+    if line + 1 >= rope.len_lines() || rope.line(line).chars().last() != Some('\n') {
+        let position = end_of_document(rope, encoding);
+
+        return TextEdit {
+            range: Range::new(position, position),
+            new_text: format!("\n{}", text),
+        };
+    }
+
+    let position = Position::new(line as u32 + 1, 0);
+
+    TextEdit {
+        range: Range::new(position, position),
+        new_text: format!("{}\n", text),
+    }
+}
+
+fn quick_fix(
+    title: String,
+    uri: &Url,
+    edits: Vec<TextEdit>,
+    diagnostics: Vec<Diagnostic>,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: (!diagnostics.is_empty()).then_some(diagnostics),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Builds a `type` declaration matching the fields used by `sc`.
+fn type_skeleton(sc: &StructConstructor) -> String {
+    let fields: Vec<(&str, String)> = sc
+        .case
+        .fields
+        .iter()
+        .map(|field| {
+            let ty = infer_type(None, &field.value).unwrap_or_else(|| "Bytes".to_string());
+            (field.name.value.as_str(), ty)
+        })
+        .collect();
+
+    // a named case means the constructor targets a variant rather than a record
+    let has_case = sc.case.name.span.start != sc.case.name.span.end;
+    let indent = if has_case { "        " } else { "    " };
+
+    let mut body: String = fields
+        .iter()
+        .map(|(name, ty)| format!("{}{}: {},\n", indent, name, ty))
+        .collect();
+
+    if has_case {
+        body = format!("    {} {{\n{}    }},\n", sc.case.name.value, body);
+    }
+
+    format!("type {} {{\n{}}}\n", sc.r#type.value, body)
+}
+
+/// Offers to declare the types used by struct constructors within `range`
+/// that don't exist yet.
+fn create_type_actions(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    range: &Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    let mut offered: Vec<&str> = Vec::new();
+
+    for sc in collect_struct_constructors(ast) {
+        let name = sc.r#type.value.as_str();
+        let type_range = span_to_lsp_range(rope, encoding, &sc.r#type.span);
+
+        if !overlaps(&type_range, range)
+            || ast.types.iter().any(|x| x.name.value == name)
+            || offered.contains(&name)
+        {
+            continue;
+        }
+
+        offered.push(name);
+
+        let related = diagnostics
+            .iter()
+            .filter(|x| overlaps(&x.range, &type_range))
+            .cloned()
+            .collect();
+
+        actions.push(quick_fix(
+            format!("Create type `{}`", name),
+            uri,
+            vec![append_edit(rope, encoding, &type_skeleton(sc))],
+            related,
+        ));
+    }
+
+    actions
+}
+
+fn address_identifiers(tx: &TxDef) -> impl Iterator<Item = &Identifier> {
+    let inputs = tx
+        .inputs
+        .iter()
+        .flat_map(|x| &x.fields)
+        .filter_map(|x| match x {
+            InputBlockField::From(expr) => Some(expr),
+            _ => None,
+        });
+
+    let outputs = tx
+        .outputs
+        .iter()
+        .flat_map(|x| &x.fields)
+        .filter_map(|x| match x {
+            OutputBlockField::To(expr) => Some(expr.as_ref()),
+            _ => None,
+        });
+
+    let collateral = tx
+        .collateral
+        .iter()
+        .flat_map(|x| &x.fields)
+        .filter_map(|x| match x {
+            CollateralBlockField::From(expr) => Some(expr),
+            _ => None,
+        });
+
+    inputs
+        .chain(outputs)
+        .chain(collateral)
+        .filter_map(|expr| match expr {
+            DataExpr::Identifier(id) => Some(id),
+            _ => None,
+        })
+}
+
+/// Offers to declare undefined names used as addresses within `range`,
+/// either as a party or as a policy.
+fn declare_address_actions(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    range: &Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    let mut offered: Vec<&str> = Vec::new();
+
+    let last_party = ast.parties.last().map(|x| &x.span);
+    let last_policy = ast.policies.last().map(|x| &x.span).or(last_party);
+
+    for tx in &ast.txs {
+        for id in address_identifiers(tx) {
+            let id_range = span_to_lsp_range(rope, encoding, &id.span);
+
+            if !overlaps(&id_range, range)
+                || is_declared(ast, tx, &id.value)
+                || offered.contains(&id.value.as_str())
+            {
+                continue;
+            }
+
+            offered.push(&id.value);
+
+            let related: Vec<Diagnostic> = diagnostics
+                .iter()
+                .filter(|x| overlaps(&x.range, &id_range))
+                .cloned()
+                .collect();
+
+            let party = format!("party {};", id.value);
+            let policy = format!("policy {} = 0x{};", id.value, "0".repeat(56));
+
+            actions.push(quick_fix(
+                format!("Declare party `{}`", id.value),
+                uri,
+                vec![insert_after_edit(rope, encoding, last_party, &party)],
+                related.clone(),
+            ));
+
+            actions.push(quick_fix(
+                format!("Declare policy `{}`", id.value),
+                uri,
+                vec![insert_after_edit(rope, encoding, last_policy, &policy)],
+                related,
+            ));
+        }
+    }
+
+    actions
+}
+
+/// Collects the identifiers within `expr` along with the type their position
+/// calls for, when it can be told from the surrounding constructors.
+fn collect_param_uses<'a>(
+    ast: &Program,
+    expr: &'a DataExpr,
+    expected: Option<&str>,
+    uses: &mut Vec<(&'a Identifier, Option<String>)>,
+) {
+    match expr {
+        DataExpr::Identifier(id) => uses.push((id, expected.map(str::to_string))),
+        DataExpr::StructConstructor(sc) => {
+            let case = resolve_constructor(ast, sc).and_then(|(_, case)| case);
+
+            for field in &sc.case.fields {
+                let ty = case
+                    .and_then(|case| {
+                        case.fields
+                            .iter()
+                            .find(|x| x.name.value == field.name.value)
+                    })
+                    .map(|x| type_to_text(&x.r#type));
+
+                collect_param_uses(ast, &field.value, ty.as_deref(), uses);
+            }
+
+            if let Some(spread) = &sc.case.spread {
+                collect_param_uses(ast, spread, Some(&sc.r#type.value), uses);
+            }
+        }
+        DataExpr::ListConstructor(x) => {
+            let inner = expected.and_then(|x| x.strip_prefix("List<")?.strip_suffix('>'));
+
+            for el in &x.elements {
+                collect_param_uses(ast, el, inner, uses);
+            }
+        }
+        // asset constructors such as `Ada(amount)` are parsed as calls
+        DataExpr::FnCall(x) => {
+            for arg in &x.args {
+                collect_param_uses(ast, arg, Some("Int"), uses);
+            }
+        }
+        DataExpr::AnyAssetConstructor(x) => {
+            collect_param_uses(ast, &x.policy, Some("Bytes"), uses);
+            collect_param_uses(ast, &x.asset_name, Some("Bytes"), uses);
+            collect_param_uses(ast, &x.amount, Some("Int"), uses);
+        }
+        DataExpr::AddOp(x) => {
+            collect_param_uses(ast, &x.lhs, expected, uses);
+            collect_param_uses(ast, &x.rhs, expected, uses);
+        }
+        DataExpr::SubOp(x) => {
+            collect_param_uses(ast, &x.lhs, expected, uses);
+            collect_param_uses(ast, &x.rhs, expected, uses);
+        }
+        DataExpr::NegateOp(x) => collect_param_uses(ast, &x.operand, expected, uses),
+        // the type of the property is known, not the one of its base
+        DataExpr::PropertyOp(x) => collect_param_uses(ast, &x.operand, None, uses),
+        _ => (),
+    }
+}
+
+/// Every identifier used within the blocks of `tx`, with the type its
+/// position calls for.
+fn tx_param_uses<'a>(ast: &Program, tx: &'a TxDef) -> Vec<(&'a Identifier, Option<String>)> {
+    let mut uses = Vec::new();
+
+    for input in &tx.inputs {
+        for field in &input.fields {
+            match field {
+                InputBlockField::From(x) => collect_param_uses(ast, x, Some("Address"), &mut uses),
+                InputBlockField::MinAmount(x) => {
+                    collect_param_uses(ast, x, Some("AnyAsset"), &mut uses)
+                }
+                InputBlockField::Ref(x) => collect_param_uses(ast, x, Some("UtxoRef"), &mut uses),
+                InputBlockField::Redeemer(x) => collect_param_uses(ast, x, None, &mut uses),
+                InputBlockField::DatumIs(_) => (),
+            }
+        }
+    }
+
+    for output in &tx.outputs {
+        for field in &output.fields {
+            match field {
+                OutputBlockField::To(x) => collect_param_uses(ast, x, Some("Address"), &mut uses),
+                OutputBlockField::Amount(x) => {
+                    collect_param_uses(ast, x, Some("AnyAsset"), &mut uses)
+                }
+                OutputBlockField::Datum(x) => collect_param_uses(ast, x, None, &mut uses),
+            }
+        }
+    }
+
+    for mint in tx.mints.iter().chain(&tx.burns) {
+        for field in &mint.fields {
+            match field {
+                MintBlockField::Amount(x) => {
+                    collect_param_uses(ast, x, Some("AnyAsset"), &mut uses)
+                }
+                MintBlockField::Redeemer(x) => collect_param_uses(ast, x, None, &mut uses),
+            }
+        }
+    }
+
+    for reference in &tx.references {
+        collect_param_uses(ast, &reference.r#ref, Some("UtxoRef"), &mut uses);
+    }
+
+    for collateral in &tx.collateral {
+        for field in &collateral.fields {
+            match field {
+                CollateralBlockField::From(x) => {
+                    collect_param_uses(ast, x, Some("Address"), &mut uses)
+                }
+                CollateralBlockField::MinAmount(x) => {
+                    collect_param_uses(ast, x, Some("AnyAsset"), &mut uses)
+                }
+                CollateralBlockField::Ref(x) => {
+                    collect_param_uses(ast, x, Some("UtxoRef"), &mut uses)
+                }
+            }
+        }
+    }
+
+    uses
+}
+
+/// Appends `name: ty` to the parameter list of `tx`, right before its closing
+/// parenthesis.
+fn add_param_edit(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    tx: &TxDef,
+    name: &str,
+    ty: &str,
+) -> TextEdit {
+    let span = &tx.parameters.span;
+    let end = span.end.min(rope.len_chars());
+    let text = rope.slice(span.start.min(end)..end).to_string();
+
+    let (insert_at, before) = match text.strip_suffix(')') {
+        Some(before) => (end - 1, before.trim_end().to_string()),
+        None => (end, text.trim_end().to_string()),
+    };
+
+    let separator = match before.trim_start_matches('(').trim() {
+        "" => "",
+        x if x.ends_with(',') => " ",
+        _ => ", ",
+    };
+
+    let (line, col) = char_index_to_line_col(rope, encoding, insert_at);
+    let position = Position::new(line as u32, col as u32);
+
+    TextEdit {
+        range: Range::new(position, position),
+        new_text: format!("{}{}: {}", separator, name, ty),
+    }
+}
+
+/// Offers to declare undefined names used within a tx body as parameters of
+/// the tx, typed after where they're used (`Bytes` when that can't be told).
+fn add_param_actions(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    range: &Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+
+    for tx in &ast.txs {
+        let mut offered: Vec<&str> = Vec::new();
+
+        for (id, ty) in tx_param_uses(ast, tx) {
+            let id_range = span_to_lsp_range(rope, encoding, &id.span);
+
+            if !overlaps(&id_range, range)
+                || is_declared(ast, tx, &id.value)
+                || offered.contains(&id.value.as_str())
+            {
+                continue;
+            }
+
+            offered.push(&id.value);
+
+            let related = diagnostics
+                .iter()
+                .filter(|x| overlaps(&x.range, &id_range))
+                .cloned()
+                .collect();
+
+            let ty = ty.unwrap_or_else(|| "Bytes".to_string());
+
+            actions.push(quick_fix(
+                format!(
+                    "Add parameter `{}: {}` to `{}`",
+                    id.value, ty, tx.name.value
+                ),
+                uri,
+                vec![add_param_edit(rope, encoding, tx, &id.value, &ty)],
+                related,
+            ));
+        }
+    }
+
+    actions
+}
+
+/// Deletes a whole statement: the span itself, a trailing `;` and, when the
+/// statement is alone on its lines, its indentation and line break too.
+fn remove_statement_edit(rope: &Rope, encoding: PositionEncoding, span: &Span) -> TextEdit {
+    let chars: Vec<char> = rope.chars().collect();
+    let is_blank = |c: &char| *c == ' ' || *c == '\t';
+
+    let mut end = span.end.min(chars.len());
+    while chars.get(end).is_some_and(is_blank) {
+        end += 1;
+    }
+    if chars.get(end) == Some(&';') {
+        end += 1;
+    }
+    while chars.get(end).is_some_and(is_blank) {
+        end += 1;
+    }
+
+    let mut start = span.start.min(end);
+    while start > 0 && is_blank(&chars[start - 1]) {
+        start -= 1;
+    }
+
+    let starts_line = start == 0 || chars[start - 1] == '\n';
+    let ends_line = end == chars.len() || chars[end] == '\n';
+
+    if starts_line && ends_line && end < chars.len() {
+        end += 1;
+    } else if !starts_line {
+        // something else shares the line, only the statement goes away
+        start = span.start;
+    }
+
+    let (start_line, start_col) = char_index_to_line_col(rope, encoding, start);
+    let (end_line, end_col) = char_index_to_line_col(rope, encoding, end);
+
+    TextEdit {
+        range: Range::new(
+            Position::new(start_line as u32, start_col as u32),
+            Position::new(end_line as u32, end_col as u32),
+        ),
+        new_text: String::new(),
+    }
+}
+
+fn is_unused_diagnostic(diagnostic: &Diagnostic) -> bool {
+    diagnostic.code == Some(NumberOrString::String(CODE_UNUSED_DECLARATION.to_string()))
+}
+
+/// Offers to delete the declarations flagged as unused, one by one and all
+/// at once as a `source.fixAll` action.
+fn remove_unused_actions(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let flagged: Vec<&Diagnostic> = diagnostics
+        .iter()
+        .filter(|x| is_unused_diagnostic(x))
+        .collect();
+
+    if flagged.is_empty() {
+        return vec![];
+    }
+
+    let unused = find_unused_declarations(ast);
+    let mut actions = Vec::new();
+
+    for diagnostic in flagged {
+        let declaration = unused
+            .iter()
+            .find(|(_, span, _)| span_to_lsp_range(rope, encoding, span) == diagnostic.range);
+
+        if let Some((name, span, _)) = declaration {
+            actions.push(quick_fix(
+                format!("Remove unused `{}`", name.value),
+                uri,
+                vec![remove_statement_edit(rope, encoding, span)],
+                vec![diagnostic.clone()],
+            ));
+        }
+    }
+
+    // diagnostics may be stale, in which case there's nothing left to remove
+    if unused.is_empty() {
+        return actions;
+    }
+
+    let edits: Vec<TextEdit> = unused
+        .iter()
+        .map(|(_, span, _)| remove_statement_edit(rope, encoding, span))
+        .collect();
+
+    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Remove all unused declarations".to_string(),
+        kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    actions
+}
+
+pub fn code_actions(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    range: &Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = create_type_actions(ast, rope, encoding, uri, range, diagnostics);
+    actions.extend(declare_address_actions(
+        ast,
+        rope,
+        encoding,
+        uri,
+        range,
+        diagnostics,
+    ));
+    actions.extend(add_param_actions(
+        ast,
+        rope,
+        encoding,
+        uri,
+        range,
+        diagnostics,
+    ));
+    actions.extend(remove_unused_actions(ast, rope, encoding, uri, diagnostics));
+    actions
+}
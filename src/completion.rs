@@ -0,0 +1,679 @@
+use ropey::Rope;
+use serde_json::json;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, Documentation,
+    InsertTextFormat, MarkupContent, MarkupKind,
+};
+use tx3_lang::ast::{
+    InputBlockField, PolicyField, PolicyValue, Program, RecordField, Span, StructConstructor,
+    TxDef, Type,
+};
+
+use crate::ast_to_text::{data_expr_to_text, type_to_text};
+use crate::visitor::{collect_asset_constructors, collect_struct_constructors, AssetConstructor};
+use crate::{doc_comment, lexer, references, span_contains};
+
+fn make_item(
+    label: &str,
+    kind: CompletionItemKind,
+    description: &str,
+    r#type: Option<String>,
+) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: Some(description.to_string()),
+        label_details: Some(CompletionItemLabelDetails {
+            detail: r#type.map(|x| format!(": {}", x)),
+            description: Some(description.to_string()),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Marks an item whose documentation is only filled in once the client asks
+/// to resolve it, keeping large completion lists cheap to build. The server
+/// adds the document uri to the payload.
+fn deferred(item: CompletionItem, kind: &str) -> CompletionItem {
+    CompletionItem {
+        data: Some(json!({ "kind": kind, "name": item.label })),
+        ..item
+    }
+}
+
+fn party_items(ast: &Program) -> impl Iterator<Item = CompletionItem> + '_ {
+    ast.parties.iter().map(|party| {
+        let item = make_item(
+            &party.name.value,
+            CompletionItemKind::CONSTANT,
+            "party",
+            None,
+        );
+        deferred(item, "party")
+    })
+}
+
+// unlike symbol kinds, completion kinds have no `KEY` to match the outline,
+// so policies use the closest one that still tells them apart from parties
+fn policy_items(ast: &Program) -> impl Iterator<Item = CompletionItem> + '_ {
+    ast.policies.iter().map(|policy| {
+        let item = make_item(
+            &policy.name.value,
+            CompletionItemKind::REFERENCE,
+            "policy",
+            None,
+        );
+        deferred(item, "policy")
+    })
+}
+
+fn type_items(ast: &Program) -> impl Iterator<Item = CompletionItem> + '_ {
+    let types = ast.types.iter().map(|ty| {
+        let cases: Vec<&str> = ty
+            .cases
+            .iter()
+            .filter(|case| case.name.span.start != case.name.span.end)
+            .map(|case| case.name.value.as_str())
+            .collect();
+
+        let description = match cases.is_empty() {
+            true => "type".to_string(),
+            false => format!("type ({})", cases.join(" | ")),
+        };
+
+        let item = make_item(
+            &ty.name.value,
+            CompletionItemKind::STRUCT,
+            &description,
+            None,
+        );
+        deferred(item, "type")
+    });
+
+    types.chain(asset_items(ast))
+}
+
+fn asset_items(ast: &Program) -> impl Iterator<Item = CompletionItem> + '_ {
+    ast.assets.iter().map(|asset| {
+        let item = make_item(&asset.name.value, CompletionItemKind::STRUCT, "asset", None);
+        deferred(item, "asset")
+    })
+}
+
+/// Fills in the documentation of an item marked as `deferred`: its doc
+/// comment followed by the source of the declaration, eg: the full definition
+/// of a type.
+pub fn resolve_item(ast: &Program, rope: &Rope, item: CompletionItem) -> CompletionItem {
+    let Some(data) = &item.data else {
+        return item;
+    };
+
+    let kind = data.get("kind").and_then(|x| x.as_str());
+    let name = data.get("name").and_then(|x| x.as_str());
+
+    let (Some(kind), Some(name)) = (kind, name) else {
+        return item;
+    };
+
+    let find = |x: &(&tx3_lang::ast::Identifier, &Span)| x.0.value == name;
+
+    let span = match kind {
+        "party" => ast.parties.iter().map(|x| (&x.name, &x.span)).find(find),
+        "policy" => ast.policies.iter().map(|x| (&x.name, &x.span)).find(find),
+        "type" => ast.types.iter().map(|x| (&x.name, &x.span)).find(find),
+        "asset" => ast.assets.iter().map(|x| (&x.name, &x.span)).find(find),
+        _ => None,
+    }
+    .map(|(_, span)| span);
+
+    let Some(span) = span else {
+        return item;
+    };
+
+    let source = format!("```tx3\n{}\n```", rope.slice(span.start..span.end));
+
+    let value = match doc_comment(rope, span) {
+        Some(doc) => format!("{}\n\n{}", doc, source),
+        None => source,
+    };
+
+    CompletionItem {
+        documentation: Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        })),
+        ..item
+    }
+}
+
+/// Policies along with what identifies them on-chain (their hash or script),
+/// for the policy argument of an asset constructor.
+fn policy_value_items(ast: &Program, rope: &Rope) -> Vec<CompletionItem> {
+    ast.policies
+        .iter()
+        .map(|policy| {
+            let value = match &policy.value {
+                PolicyValue::Assign(x) => format!("hash: {}", rope.slice(x.span.start..x.span.end)),
+                PolicyValue::Constructor(x) => x
+                    .fields
+                    .iter()
+                    .map(|field| match field {
+                        PolicyField::Hash(x) => format!("hash: {}", data_expr_to_text(x)),
+                        PolicyField::Script(x) => format!("script: {}", data_expr_to_text(x)),
+                        PolicyField::Ref(x) => format!("ref: {}", data_expr_to_text(x)),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+
+            let documentation = match doc_comment(rope, &policy.span) {
+                Some(doc) => format!("{}\n\n{}", doc, value),
+                None => value,
+            };
+
+            CompletionItem {
+                documentation: Some(Documentation::String(documentation)),
+                ..make_item(
+                    &policy.name.value,
+                    CompletionItemKind::REFERENCE,
+                    "policy",
+                    None,
+                )
+            }
+        })
+        .collect()
+}
+
+/// Index of the argument being typed at `offset` within the parenthesized
+/// argument list starting after `start`.
+fn argument_index(rope: &Rope, start: usize, offset: usize) -> Option<usize> {
+    let offset = offset.min(rope.len_chars());
+    let text = rope.slice(start.min(offset)..offset).to_string();
+
+    let (_, args) = text.split_once('(')?;
+
+    let mut depth = 0;
+    let mut index = 0;
+
+    for c in args.chars() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => index += 1,
+            _ => {}
+        }
+    }
+
+    Some(index)
+}
+
+/// Completes the arguments of the innermost asset constructor around the
+/// cursor: assets for the name of a named one, policies or assets for the
+/// first two arguments of `AnyAsset`.
+fn asset_constructor_items(
+    ast: &Program,
+    rope: &Rope,
+    offset: usize,
+) -> Option<Vec<CompletionItem>> {
+    let constructor = collect_asset_constructors(ast)
+        .into_iter()
+        .filter(|x| match x {
+            AssetConstructor::Call(x) => span_contains(&x.span, offset),
+            AssetConstructor::Any(x) => span_contains(&x.span, offset),
+        })
+        .min_by_key(|x| match x {
+            AssetConstructor::Call(x) => x.span.end - x.span.start,
+            AssetConstructor::Any(x) => x.span.end - x.span.start,
+        })?;
+
+    match constructor {
+        AssetConstructor::Call(x) => {
+            let at_name = x.callee.span.start <= offset && offset <= x.callee.span.end;
+            at_name.then(|| asset_items(ast).collect())
+        }
+        AssetConstructor::Any(x) => match argument_index(rope, x.span.start, offset)? {
+            0 => Some(policy_value_items(ast, rope)),
+            1 => Some(asset_items(ast).collect()),
+            _ => None,
+        },
+    }
+}
+
+fn record_field_items(ast: &Program, sc: &StructConstructor) -> Vec<CompletionItem> {
+    let Some((_, Some(case))) = references::resolve_constructor(ast, sc) else {
+        return vec![];
+    };
+
+    case.fields
+        .iter()
+        .filter(|field| {
+            !sc.case
+                .fields
+                .iter()
+                .any(|x| x.name.value == field.name.value)
+        })
+        .map(|field| {
+            make_item(
+                &field.name.value,
+                CompletionItemKind::FIELD,
+                &type_to_text(&field.r#type),
+                Some(type_to_text(&field.r#type)),
+            )
+        })
+        .collect()
+}
+
+fn input_items(tx: &TxDef) -> impl Iterator<Item = CompletionItem> + '_ {
+    tx.inputs
+        .iter()
+        .map(|input| make_item(&input.name, CompletionItemKind::VARIABLE, "input", None))
+}
+
+fn parameter_items(tx: &TxDef) -> impl Iterator<Item = CompletionItem> + '_ {
+    tx.parameters.parameters.iter().map(|param| {
+        make_item(
+            &param.name.value,
+            CompletionItemKind::VARIABLE,
+            "parameter",
+            Some(type_to_text(&param.r#type)),
+        )
+    })
+}
+
+fn reference_block_items(ast: &Program, tx: &TxDef) -> Vec<CompletionItem> {
+    party_items(ast)
+        .chain(policy_items(ast))
+        .chain(input_items(tx))
+        .chain(parameter_items(tx))
+        .collect()
+}
+
+fn address_items(ast: &Program) -> Vec<CompletionItem> {
+    party_items(ast).chain(policy_items(ast)).collect()
+}
+
+/// Splits the property path being typed at `offset`, eg: `["a", "b"]` for
+/// `a.b.c|`, along with the offset of the dot before the partial segment.
+fn property_path(rope: &Rope, offset: usize) -> Option<(Vec<String>, usize)> {
+    let offset = offset.min(rope.len_chars());
+    let line_start = rope.line_to_char(rope.char_to_line(offset));
+    let before: Vec<char> = rope.slice(line_start..offset).chars().collect();
+
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+
+    let partial = before.iter().rev().take_while(|c| is_word(c)).count();
+    let dot = before.len().checked_sub(partial + 1)?;
+
+    if before[dot] != '.' {
+        return None;
+    }
+
+    let base: String = before[..dot]
+        .iter()
+        .rev()
+        .take_while(|c| is_word(c) || **c == '.')
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+
+    let segments: Vec<String> = base.split('.').map(str::to_string).collect();
+
+    if segments.iter().any(String::is_empty) {
+        return None;
+    }
+
+    Some((segments, line_start + dot))
+}
+
+/// The fields of a record type, which are the only ones that can be reached
+/// through a property access.
+fn record_fields<'a>(ast: &'a Program, ty: &Type) -> Option<&'a [RecordField]> {
+    let Type::Custom(name) = ty else {
+        return None;
+    };
+
+    let ty = ast.types.iter().find(|x| x.name.value == name.value)?;
+
+    match &ty.cases[..] {
+        [case] => Some(&case.fields),
+        _ => None,
+    }
+}
+
+/// The type a property path starts from: a parameter or the datum of an input.
+fn property_base_type<'a>(tx: &'a TxDef, name: &str) -> Option<&'a Type> {
+    let param = tx
+        .parameters
+        .parameters
+        .iter()
+        .find(|x| x.name.value == name)
+        .map(|x| &x.r#type);
+
+    let datum = || {
+        tx.inputs
+            .iter()
+            .filter(|x| x.name == name)
+            .flat_map(|x| &x.fields)
+            .find_map(|field| match field {
+                InputBlockField::DatumIs(ty) => Some(ty),
+                _ => None,
+            })
+    };
+
+    param.or_else(datum)
+}
+
+/// Offers the fields reachable after a dot, eg: `param.` or `source.a.b.`,
+/// following the path through the record types it goes through.
+///
+/// The document rarely parses while a property is being typed, so `ast` is
+/// usually the one of its last version that did. Its spans may be off by the
+/// few chars typed since, so the tx around the dot is only preferred among
+/// those declaring the base.
+pub fn property_items(ast: &Program, rope: &Rope, offset: usize) -> Option<Vec<CompletionItem>> {
+    let (segments, dot) = property_path(rope, offset)?;
+    let (base, path) = segments.split_first()?;
+
+    let declaring = ast
+        .txs
+        .iter()
+        .filter(|tx| property_base_type(tx, base).is_some());
+
+    let tx = declaring
+        .clone()
+        .find(|tx| span_contains(&tx.span, dot))
+        .or_else(|| declaring.clone().next())?;
+
+    let mut fields = record_fields(ast, property_base_type(tx, base)?)?;
+
+    for segment in path {
+        let field = fields.iter().find(|x| &x.name.value == segment)?;
+        fields = record_fields(ast, &field.r#type)?;
+    }
+
+    let items = fields
+        .iter()
+        .map(|field| {
+            make_item(
+                &field.name.value,
+                CompletionItemKind::FIELD,
+                &type_to_text(&field.r#type),
+                Some(type_to_text(&field.r#type)),
+            )
+        })
+        .collect();
+
+    Some(items)
+}
+
+/// Returns the name of the block field whose value is being typed at
+/// `offset`, e.g. `to` for `to: Ali|`.
+fn field_at(rope: &Rope, offset: usize) -> Option<String> {
+    let offset = offset.min(rope.len_chars());
+    let line_start = rope.line_to_char(rope.char_to_line(offset));
+    let before = rope.slice(line_start..offset).to_string();
+
+    let entry = before.rsplit([',', '{']).next()?;
+    let (name, _) = entry.split_once(':')?;
+
+    Some(name.trim().to_string())
+}
+
+const TOP_LEVEL_SNIPPETS: &[(&str, &str)] = &[
+    ("party", "party ${1:Name};"),
+    ("policy", "policy ${1:Name} = 0x${2};"),
+    ("type", "type ${1:Name} {\n\t${2:field}: ${3:Int},\n}"),
+    ("asset", "asset ${1:Name} = 0x${2}.${3:name};"),
+    ("tx", "tx ${1:name}(${2}) {\n\t$0\n}"),
+];
+
+const TX_BODY_SNIPPETS: &[(&str, &str)] = &[
+    (
+        "input",
+        "input ${1:source} {\n\tfrom: $2,\n\tmin_amount: $3,\n}",
+    ),
+    ("output", "output {\n\tto: $1,\n\tamount: $2,\n}"),
+    ("mint", "mint {\n\tamount: $1,\n\tredeemer: $2,\n}"),
+    ("burn", "burn {\n\tamount: $1,\n\tredeemer: $2,\n}"),
+    ("reference", "reference ${1:name} {\n\tref: $2,\n}"),
+    (
+        "collateral",
+        "collateral {\n\tfrom: $1,\n\tmin_amount: $2,\n}",
+    ),
+    ("signers", "signers {\n\t$1,\n}"),
+    (
+        "validity",
+        "validity {\n\tsince_slot: $1,\n\tuntil_slot: $2,\n}",
+    ),
+    ("metadata", "metadata {\n\t$1: $2,\n}"),
+];
+
+fn snippet_items(snippets: &[(&str, &str)]) -> Vec<CompletionItem> {
+    snippets
+        .iter()
+        .map(|(keyword, snippet)| CompletionItem {
+            insert_text: Some(snippet.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..make_item(keyword, CompletionItemKind::KEYWORD, "keyword", None)
+        })
+        .collect()
+}
+
+/// Returns the leading keyword of each block enclosing `offset`, outermost
+/// first, e.g. `["tx", "input"]` inside an input block. Works on the raw text
+/// so it still applies while the document doesn't parse.
+fn enclosing_blocks(rope: &Rope, offset: usize) -> Vec<String> {
+    let text = rope.slice(..offset.min(rope.len_chars())).to_string();
+
+    // tokens come in order, so a single cursor tells whether a char is commented
+    let mut comments = lexer::scan(&text)
+        .into_iter()
+        .filter(|x| x.kind == lexer::TokenKind::Comment)
+        .peekable();
+
+    let mut blocks = Vec::new();
+    let mut statement: Option<String> = None;
+    let mut word = String::new();
+    let mut in_string = false;
+
+    for (i, c) in text.chars().enumerate() {
+        while comments.next_if(|x| x.end <= i).is_some() {}
+
+        if comments.peek().is_some_and(|x| x.start <= i) {
+            continue;
+        }
+
+        if in_string {
+            in_string = c != '"';
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+
+        if !word.is_empty() {
+            statement.get_or_insert(std::mem::take(&mut word));
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => blocks.push(statement.take().unwrap_or_default()),
+            '}' => {
+                blocks.pop();
+                statement = None;
+            }
+            ',' | ';' => statement = None,
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Offers keywords and block skeletons when the cursor is at the start of a
+/// statement, either at the top level or directly inside a tx body.
+pub fn keyword_items(rope: &Rope, offset: usize) -> Vec<CompletionItem> {
+    let offset = offset.min(rope.len_chars());
+    let line_start = rope.line_to_char(rope.char_to_line(offset));
+    let before = rope.slice(line_start..offset).to_string();
+
+    // anything other than a partially typed word means we're inside an expression
+    let at_statement = before
+        .trim_start()
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_');
+
+    if !at_statement {
+        return vec![];
+    }
+
+    let blocks = enclosing_blocks(rope, offset);
+
+    match blocks.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => snippet_items(TOP_LEVEL_SNIPPETS),
+        ["tx"] => snippet_items(TX_BODY_SNIPPETS),
+        _ => vec![],
+    }
+}
+
+pub fn completions_at(ast: &Program, rope: &Rope, offset: usize) -> Vec<CompletionItem> {
+    let constructors = collect_struct_constructors(ast);
+
+    // the type name of a struct constructor, completed while it's being typed
+    let at_constructor_type = constructors
+        .iter()
+        .any(|sc| sc.r#type.span.start <= offset && offset <= sc.r#type.span.end);
+
+    if at_constructor_type {
+        return type_items(ast).collect();
+    }
+
+    if let Some(items) = asset_constructor_items(ast, rope, offset) {
+        return items;
+    }
+
+    // the innermost constructor whose field list surrounds the cursor, as long as
+    // the cursor is at a field name rather than inside a field value
+    let enclosing = constructors
+        .iter()
+        .filter(|sc| span_contains(&sc.span, offset))
+        .min_by_key(|sc| sc.span.end - sc.span.start);
+
+    if let Some(sc) = enclosing {
+        if field_at(rope, offset).is_none() {
+            return record_field_items(ast, sc);
+        }
+    }
+
+    for tx in &ast.txs {
+        if !span_contains(&tx.span, offset) {
+            continue;
+        }
+
+        let field = field_at(rope, offset);
+
+        if tx.inputs.iter().any(|x| span_contains(&x.span, offset)) {
+            match field.as_deref() {
+                Some("from") => return address_items(ast),
+                Some("datum_is") => return type_items(ast).collect(),
+                _ => {}
+            }
+        }
+
+        if tx.outputs.iter().any(|x| span_contains(&x.span, offset)) {
+            match field.as_deref() {
+                Some("to") => return address_items(ast),
+                Some("datum") => return type_items(ast).collect(),
+                _ => {}
+            }
+        }
+
+        for reference in &tx.references {
+            if span_contains(&reference.span, offset) {
+                return reference_block_items(ast, tx);
+            }
+        }
+    }
+
+    keyword_items(rope, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Completes at the end of the first occurrence of `after` in `text`.
+    fn complete(text: &str, after: &str) -> Vec<CompletionItem> {
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+        let rope = Rope::from_str(text);
+        let offset = text.find(after).unwrap() + after.len();
+
+        completions_at(&ast, &rope, offset)
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<(&str, CompletionItemKind)> {
+        items
+            .iter()
+            .map(|x| (x.label.as_str(), x.kind.unwrap()))
+            .collect()
+    }
+
+    const REFERENCE: &str = r#"party Sender;
+policy Token = 0x0011;
+
+tx lock(utxo: UtxoRef) {
+    input source {
+        from: Sender,
+        min_amount: Ada(1),
+    }
+    reference script {
+        ref: utxo,
+    }
+}
+"#;
+
+    #[test]
+    fn reference_blocks_offer_what_a_reference_can_point_to() {
+        let items = complete(REFERENCE, "ref: ");
+
+        assert_eq!(
+            labels(&items),
+            vec![
+                ("Sender", CompletionItemKind::CONSTANT),
+                ("Token", CompletionItemKind::REFERENCE),
+                ("source", CompletionItemKind::VARIABLE),
+                ("utxo", CompletionItemKind::VARIABLE),
+            ]
+        );
+    }
+
+    #[test]
+    fn items_show_their_type_and_kind_inline() {
+        let items = complete(REFERENCE, "ref: ");
+        let details = |label: &str| {
+            items
+                .iter()
+                .find(|x| x.label == label)
+                .and_then(|x| x.label_details.clone())
+                .unwrap()
+        };
+
+        assert_eq!(
+            details("utxo"),
+            CompletionItemLabelDetails {
+                detail: Some(": UtxoRef".to_string()),
+                description: Some("parameter".to_string()),
+            }
+        );
+        assert_eq!(
+            details("Sender"),
+            CompletionItemLabelDetails {
+                detail: None,
+                description: Some("party".to_string()),
+            }
+        );
+    }
+}
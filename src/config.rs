@@ -0,0 +1,166 @@
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::ast_to_svg::DiagramTheme;
+
+/// Server settings sent by the client as `initializationOptions`. Missing
+/// fields keep their default value.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Report txs that fail to lower into TIR, on top of the analyzer errors.
+    pub enable_lowering_diagnostics: bool,
+
+    /// How long a document has to go without changes before it's re-analyzed.
+    pub debounce_ms: u64,
+
+    /// Colors of the generated diagrams, either `dark`, `light` or custom
+    /// `{ stroke, text, background }` colors.
+    pub diagram_theme: DiagramTheme,
+
+    /// Mark identifiers that don't resolve to any declaration with the
+    /// `invalid` semantic token modifier. Off by default since themes rarely
+    /// style it.
+    pub highlight_undefined_symbols: bool,
+
+    /// Warn about parties, policies, types and assets that are never used.
+    pub warn_unused_declarations: bool,
+
+    /// Explorer URL for address literals, with `{addr}` standing for the
+    /// address. Addresses aren't linked when unset.
+    pub address_explorer_url: Option<String>,
+
+    /// Explorer URL for policy hashes, with `{policy}` standing for the hash.
+    /// Policies aren't linked when unset.
+    pub policy_explorer_url: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enable_lowering_diagnostics: true,
+            debounce_ms: 200,
+            diagram_theme: DiagramTheme::dark(),
+            highlight_undefined_symbols: false,
+            warn_unused_declarations: false,
+            address_explorer_url: None,
+            policy_explorer_url: None,
+        }
+    }
+}
+
+/// Deserializes `options[key]` into `target` when present, leaving the
+/// default in place and recording `key` as rejected when it doesn't match.
+fn read_field<T: DeserializeOwned>(
+    options: &Map<String, Value>,
+    key: &str,
+    target: &mut T,
+    rejected: &mut Vec<String>,
+) {
+    let Some(value) = options.get(key) else {
+        return;
+    };
+
+    match T::deserialize(value) {
+        Ok(x) => *target = x,
+        Err(_) => rejected.push(key.to_string()),
+    }
+}
+
+impl Config {
+    /// Parses the client options field by field, so that a malformed field
+    /// only falls back to its own default. Returns the names of the fields
+    /// that were rejected along with the config.
+    pub fn from_initialization_options(options: Option<&Value>) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut rejected = Vec::new();
+
+        let options = match options {
+            None | Some(Value::Null) => return (config, rejected),
+            Some(Value::Object(x)) => x,
+            Some(_) => return (config, vec!["initializationOptions".to_string()]),
+        };
+
+        read_field(
+            options,
+            "enable_lowering_diagnostics",
+            &mut config.enable_lowering_diagnostics,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "debounce_ms",
+            &mut config.debounce_ms,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "diagram_theme",
+            &mut config.diagram_theme,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "highlight_undefined_symbols",
+            &mut config.highlight_undefined_symbols,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "warn_unused_declarations",
+            &mut config.warn_unused_declarations,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "address_explorer_url",
+            &mut config.address_explorer_url,
+            &mut rejected,
+        );
+        read_field(
+            options,
+            "policy_explorer_url",
+            &mut config.policy_explorer_url,
+            &mut rejected,
+        );
+
+        (config, rejected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn malformed_fields_only_reset_themselves() {
+        let options = json!({
+            "debounce_ms": "fast",
+            "warn_unused_declarations": true,
+            "address_explorer_url": "https://explorer/{addr}",
+            "diagram_theme": 42,
+        });
+
+        let (config, rejected) = Config::from_initialization_options(Some(&options));
+
+        assert_eq!(rejected, vec!["debounce_ms", "diagram_theme"]);
+        assert_eq!(config.debounce_ms, 200);
+        assert!(config.warn_unused_declarations);
+        assert_eq!(
+            config.address_explorer_url.as_deref(),
+            Some("https://explorer/{addr}")
+        );
+    }
+
+    #[test]
+    fn options_that_arent_an_object_are_rejected_whole() {
+        let (config, rejected) = Config::from_initialization_options(Some(&json!([1, 2])));
+
+        assert_eq!(rejected, vec!["initializationOptions"]);
+        assert!(config.enable_lowering_diagnostics);
+
+        let (_, rejected) = Config::from_initialization_options(None);
+        assert!(rejected.is_empty());
+    }
+}
@@ -0,0 +1,431 @@
+use tx3_lang::ast::DataExpr;
+use tx3_lang::ast::Identifier;
+use tx3_lang::ast::InputBlockField;
+use tx3_lang::ast::MintBlockField;
+use tx3_lang::ast::OutputBlockField;
+use tx3_lang::ast::PolicyField;
+use tx3_lang::ast::PolicyValue;
+use tx3_lang::ast::Program;
+use tx3_lang::ast::TxDef;
+
+use crate::ast_to_text::data_expr_to_text;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PartyType {
+    Unknown,
+    Party,
+    Policy,
+    /// A policy backed by a script, which funds can be locked into.
+    Script,
+    Parameter,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Party {
+    pub name: String,
+    pub party_type: PartyType,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Parameter {
+    pub name: String,
+    pub party: Option<String>,
+    pub amount: Option<String>,
+    pub datum: Option<String>,
+}
+
+fn infer_party_type(program: &Program, tx: &TxDef, name: &str) -> PartyType {
+    // tx parameters shadow top-level declarations
+    if tx
+        .parameters
+        .parameters
+        .iter()
+        .any(|param| param.name.value == name)
+    {
+        PartyType::Parameter
+    } else if let Some(policy) = program
+        .policies
+        .iter()
+        .find(|policy| policy.name.value == name)
+    {
+        let has_script = match &policy.value {
+            PolicyValue::Constructor(x) => x
+                .fields
+                .iter()
+                .any(|field| matches!(field, PolicyField::Script(_))),
+            PolicyValue::Assign(_) => false,
+        };
+
+        match has_script {
+            true => PartyType::Script,
+            false => PartyType::Policy,
+        }
+    } else if program.parties.iter().any(|party| party.name.value == name) {
+        PartyType::Party
+    } else {
+        PartyType::Unknown
+    }
+}
+
+fn get_input_parties(ast: &Program, tx: &TxDef) -> Vec<Party> {
+    let mut names = std::collections::HashSet::new();
+
+    for input in &tx.inputs {
+        for field in &input.fields {
+            if let InputBlockField::From(address_expr) = field {
+                if let Some(identifier) = address_expr.as_identifier() {
+                    names.insert(identifier.value.clone());
+                }
+            }
+        }
+    }
+
+    let mut parties: Vec<Party> = names
+        .into_iter()
+        .map(|name| Party {
+            name: name.clone(),
+            party_type: infer_party_type(ast, tx, &name),
+        })
+        .collect();
+
+    parties.sort_by_key(|p| p.name.clone());
+
+    parties
+}
+
+fn get_output_parties(ast: &Program, tx: &TxDef) -> Vec<Party> {
+    let mut names = std::collections::HashSet::new();
+
+    for output in &tx.outputs {
+        for field in &output.fields {
+            if let OutputBlockField::To(address_expr) = field {
+                if let Some(identifier) = address_expr.as_identifier() {
+                    names.insert(identifier.value.clone());
+                }
+            }
+        }
+    }
+
+    let mut parties: Vec<Party> = names
+        .into_iter()
+        .map(|name| Party {
+            name: name.clone(),
+            party_type: infer_party_type(ast, tx, &name),
+        })
+        .collect();
+
+    parties.sort_by_key(|p| p.name.clone());
+
+    parties
+}
+
+fn get_inputs(tx: &TxDef) -> Vec<Parameter> {
+    tx.inputs
+        .iter()
+        .map(|input| {
+            let name = input.name.clone();
+            let party = input.fields.iter().find_map(|f| {
+                if let InputBlockField::From(address_expr) = f {
+                    address_expr
+                        .as_identifier()
+                        .map(|ident| ident.value.clone())
+                } else {
+                    None
+                }
+            });
+            Parameter {
+                name,
+                party,
+                amount: None,
+                datum: None,
+            }
+        })
+        .collect()
+}
+
+fn get_outputs(tx: &TxDef) -> Vec<Parameter> {
+    tx.outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| {
+            let name = output
+                .clone()
+                .name
+                .unwrap_or(Identifier::new(format!("output {}", i + 1)))
+                .value;
+
+            let party = output.fields.iter().find_map(|f| {
+                if let OutputBlockField::To(address_expr) = f {
+                    address_expr
+                        .as_ref()
+                        .as_identifier()
+                        .map(|ident| ident.value.clone())
+                } else {
+                    None
+                }
+            });
+
+            let amount = output.fields.iter().find_map(|f| match f {
+                OutputBlockField::Amount(expr) => Some(data_expr_to_text(expr)),
+                _ => None,
+            });
+
+            // the datum is summarized by its type when it's built through a constructor
+            let datum = output.fields.iter().find_map(|f| match f {
+                OutputBlockField::Datum(expr) => match expr.as_ref() {
+                    DataExpr::StructConstructor(x) => Some(x.r#type.value.clone()),
+                    other => Some(data_expr_to_text(other)),
+                },
+                _ => None,
+            });
+
+            Parameter {
+                name,
+                party,
+                amount,
+                datum,
+            }
+        })
+        .collect()
+}
+
+/// A mint or burn, along with the policy controlling the asset.
+#[derive(Debug, Clone)]
+pub(crate) struct SupplyChange {
+    pub policy: Party,
+    pub amount: String,
+    pub is_burn: bool,
+}
+
+fn asset_policy_name(ast: &Program, expr: &DataExpr) -> Option<String> {
+    let policy = match expr {
+        DataExpr::FnCall(x) => {
+            &ast.assets
+                .iter()
+                .find(|asset| asset.name.value == x.callee.value)?
+                .policy
+        }
+        DataExpr::AnyAssetConstructor(x) => x.policy.as_ref(),
+        _ => return None,
+    };
+
+    policy.as_identifier().map(|x| x.value.clone())
+}
+
+fn get_supply_changes(ast: &Program, tx: &TxDef) -> Vec<SupplyChange> {
+    let mints = tx.mints.iter().map(|x| (x, false));
+    let burns = tx.burns.iter().map(|x| (x, true));
+
+    mints
+        .chain(burns)
+        .filter_map(|(block, is_burn)| {
+            let amount = block.fields.iter().find_map(|f| match f {
+                MintBlockField::Amount(expr) => Some(expr),
+                _ => None,
+            })?;
+
+            let policy = match asset_policy_name(ast, amount) {
+                Some(name) => Party {
+                    party_type: infer_party_type(ast, tx, &name),
+                    name,
+                },
+                None => Party {
+                    name: "?".to_string(),
+                    party_type: PartyType::Unknown,
+                },
+            };
+
+            Some(SupplyChange {
+                policy,
+                amount: data_expr_to_text(amount),
+                is_burn,
+            })
+        })
+        .collect()
+}
+
+/// Everything a diagram shows of a tx, independent of the format it's
+/// rendered into.
+#[derive(Debug, Clone)]
+pub(crate) struct TxGraph {
+    pub name: String,
+    /// Parties funding the inputs, sorted by name.
+    pub input_parties: Vec<Party>,
+    /// Parties receiving the outputs, sorted by name.
+    pub output_parties: Vec<Party>,
+    pub inputs: Vec<Parameter>,
+    pub outputs: Vec<Parameter>,
+    pub supply_changes: Vec<SupplyChange>,
+}
+
+impl TxGraph {
+    pub fn from_tx(ast: &Program, tx: &TxDef) -> Self {
+        Self {
+            name: tx.name.value.clone(),
+            input_parties: get_input_parties(ast, tx),
+            output_parties: get_output_parties(ast, tx),
+            inputs: get_inputs(tx),
+            outputs: get_outputs(tx),
+            supply_changes: get_supply_changes(ast, tx),
+        }
+    }
+
+    /// Links each input to the party it comes from, as `(input, party)`
+    /// indexes into `inputs` and `input_parties`.
+    pub fn input_edges(&self) -> Vec<(usize, usize)> {
+        edges(&self.inputs, &self.input_parties)
+    }
+
+    /// Links each output to the party it goes to, as `(output, party)`
+    /// indexes into `outputs` and `output_parties`.
+    pub fn output_edges(&self) -> Vec<(usize, usize)> {
+        edges(&self.outputs, &self.output_parties)
+    }
+}
+
+fn edges(parameters: &[Parameter], parties: &[Party]) -> Vec<(usize, usize)> {
+    parameters
+        .iter()
+        .enumerate()
+        .filter_map(|(i, parameter)| {
+            let name = parameter.party.as_ref()?;
+            let party = parties.iter().position(|p| &p.name == name)?;
+            Some((i, party))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(text: &str) -> TxGraph {
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+        TxGraph::from_tx(&ast, &ast.txs[0])
+    }
+
+    #[test]
+    fn parties_bound_through_parameters_are_labeled_as_such() {
+        let graph = graph(
+            r#"party Sender;
+
+tx pay(receiver: Address, quantity: Int) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: receiver,
+        amount: Ada(quantity),
+    }
+}
+"#,
+        );
+
+        assert_eq!(graph.output_parties.len(), 1);
+        assert_eq!(graph.output_parties[0].name, "receiver");
+        assert_eq!(graph.output_parties[0].party_type, PartyType::Parameter);
+        assert_eq!(graph.output_edges(), vec![(0, 0)]);
+
+        assert_eq!(graph.input_parties[0].party_type, PartyType::Party);
+    }
+
+    #[test]
+    fn the_graph_holds_everything_a_diagram_draws() {
+        let graph = graph(
+            r#"party Sender;
+party Receiver;
+policy Minter = 0x0011;
+
+type Order {
+    amount: Int,
+}
+
+tx trade(quantity: Int) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    mint {
+        amount: AnyAsset(Minter, 0x544f4b454e, 10),
+    }
+    output {
+        to: Receiver,
+        amount: Ada(quantity),
+        datum: Order { amount: quantity },
+    }
+    output {
+        to: Sender,
+        amount: source - Ada(quantity),
+    }
+}
+"#,
+        );
+
+        assert_eq!(graph.name, "trade");
+
+        let parties = |parties: &[Party]| {
+            parties
+                .iter()
+                .map(|x| (x.name.clone(), x.party_type.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parties(&graph.input_parties),
+            vec![("Sender".to_string(), PartyType::Party)]
+        );
+        assert_eq!(
+            parties(&graph.output_parties),
+            vec![
+                ("Receiver".to_string(), PartyType::Party),
+                ("Sender".to_string(), PartyType::Party),
+            ]
+        );
+
+        assert_eq!(graph.inputs.len(), 1);
+        assert_eq!(graph.inputs[0].name, "source");
+        assert_eq!(graph.inputs[0].party.as_deref(), Some("Sender"));
+
+        let outputs: Vec<_> = graph
+            .outputs
+            .iter()
+            .map(|x| {
+                (
+                    x.name.as_str(),
+                    x.party.as_deref(),
+                    x.amount.as_deref(),
+                    x.datum.as_deref(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            outputs,
+            vec![
+                (
+                    "output 1",
+                    Some("Receiver"),
+                    Some("Ada(quantity)"),
+                    Some("Order")
+                ),
+                (
+                    "output 2",
+                    Some("Sender"),
+                    Some("source - Ada(quantity)"),
+                    None
+                ),
+            ]
+        );
+
+        assert_eq!(graph.input_edges(), vec![(0, 0)]);
+        assert_eq!(graph.output_edges(), vec![(0, 0), (1, 1)]);
+
+        assert_eq!(graph.supply_changes.len(), 1);
+        let change = &graph.supply_changes[0];
+        assert_eq!(change.policy.name, "Minter");
+        assert_eq!(change.policy.party_type, PartyType::Policy);
+        assert_eq!(change.amount, "AnyAsset(Minter, 0x544f4b454e, 10)");
+        assert!(!change.is_burn);
+    }
+}
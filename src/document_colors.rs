@@ -0,0 +1,47 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{Color, ColorInformation};
+use tx3_lang::ast::Program;
+
+use crate::visitor::{collect_literals, LiteralKind};
+use crate::{span_to_lsp_range, PositionEncoding};
+
+/// Derives a stable color from the text of a literal (FNV-1a), so the same
+/// bytes always get the same chip and different ones rarely collide.
+fn literal_color(text: &str) -> Color {
+    let mut hash: u32 = 0x811c9dc5;
+
+    for byte in text.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    let [r, g, b, _] = hash.to_be_bytes();
+
+    Color {
+        red: r as f32 / 255.0,
+        green: g as f32 / 255.0,
+        blue: b as f32 / 255.0,
+        alpha: 1.0,
+    }
+}
+
+/// Colors every bytes literal (hex strings, utxo refs and policy hashes) to
+/// help tell them apart at a glance.
+pub fn document_colors(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<ColorInformation> {
+    collect_literals(ast)
+        .into_iter()
+        .filter(|x| x.kind == LiteralKind::Bytes)
+        .map(|literal| {
+            let text = rope.slice(literal.span.start..literal.span.end).to_string();
+
+            ColorInformation {
+                range: span_to_lsp_range(rope, encoding, literal.span),
+                color: literal_color(&text),
+            }
+        })
+        .collect()
+}
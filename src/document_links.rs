@@ -0,0 +1,83 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{DocumentLink, Url};
+use tx3_lang::ast::{DataExpr, PolicyField, PolicyValue, Program, Span};
+
+use crate::visitor::{collect_literals, LiteralKind};
+use crate::{span_to_lsp_range, PositionEncoding};
+
+/// The source text of a literal, without quotes nor `0x` prefix.
+fn literal_text(rope: &Rope, span: &Span) -> String {
+    let text = rope.slice(span.start..span.end).to_string();
+    let text = text.trim_matches('"');
+
+    text.strip_prefix("0x").unwrap_or(text).to_string()
+}
+
+fn make_link(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    span: &Span,
+    template: &str,
+    placeholder: &str,
+) -> Option<DocumentLink> {
+    let value = literal_text(rope, span);
+
+    if value.is_empty() {
+        return None;
+    }
+
+    let target = Url::parse(&template.replace(placeholder, &value)).ok()?;
+
+    Some(DocumentLink {
+        range: span_to_lsp_range(rope, encoding, span),
+        target: Some(target),
+        tooltip: Some(format!("Open {} in explorer", value)),
+        data: None,
+    })
+}
+
+/// Links address literals and policy hashes to a block explorer. Each kind
+/// only gets links if the client configured a template for it, with `{addr}`
+/// or `{policy}` standing for the value.
+pub fn document_links(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    address_template: Option<&str>,
+    policy_template: Option<&str>,
+) -> Vec<DocumentLink> {
+    let mut links = Vec::new();
+
+    if let Some(template) = address_template {
+        for literal in collect_literals(ast) {
+            if literal.kind != LiteralKind::String {
+                continue;
+            }
+
+            // bech32 addresses, for mainnet and testnets alike
+            if !literal_text(rope, literal.span).starts_with("addr") {
+                continue;
+            }
+
+            links.extend(make_link(rope, encoding, literal.span, template, "{addr}"));
+        }
+    }
+
+    if let Some(template) = policy_template {
+        for policy in &ast.policies {
+            let span = match &policy.value {
+                PolicyValue::Assign(x) => Some(&x.span),
+                PolicyValue::Constructor(x) => x.fields.iter().find_map(|field| match field {
+                    PolicyField::Hash(DataExpr::HexString(x)) => Some(&x.span),
+                    _ => None,
+                }),
+            };
+
+            if let Some(span) = span {
+                links.extend(make_link(rope, encoding, span, template, "{policy}"));
+            }
+        }
+    }
+
+    links
+}
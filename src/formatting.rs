@@ -0,0 +1,331 @@
+use serde_json::Value;
+use tx3_lang::ast::{Program, Span};
+
+const INDENT: &str = "    ";
+
+/// Where a line starts, as far as the formatter is concerned.
+struct LineInfo {
+    /// Number of brackets left open by the previous lines.
+    depth: usize,
+    /// The line starts within a block comment or a multi-line string, so it's
+    /// kept untouched.
+    verbatim: bool,
+}
+
+/// Scans the source for the bracket depth at the start of each line, skipping
+/// brackets within comments and strings.
+fn scan_lines(text: &str) -> Vec<LineInfo> {
+    let mut lines = vec![LineInfo {
+        depth: 0,
+        verbatim: false,
+    }];
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut in_block_comment = false;
+    let mut in_line_comment = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c == '\n' {
+            in_line_comment = false;
+            lines.push(LineInfo {
+                depth,
+                verbatim: in_string || in_block_comment,
+            });
+        } else if in_line_comment {
+            // nothing to track until the end of the line
+        } else if in_block_comment {
+            if c == '*' && next == Some('/') {
+                in_block_comment = false;
+                i += 1;
+            }
+        } else if in_string {
+            match c {
+                '\\' => i += 1,
+                '"' => in_string = false,
+                _ => (),
+            }
+        } else {
+            match (c, next) {
+                ('/', Some('/')) => in_line_comment = true,
+                ('/', Some('*')) => {
+                    in_block_comment = true;
+                    i += 1;
+                }
+                ('"', _) => in_string = true,
+                ('{' | '(' | '[', _) => depth += 1,
+                ('}' | ')' | ']', _) => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+        }
+
+        i += 1;
+    }
+
+    lines
+}
+
+/// Brackets closed at the start of a line, which dedent the line itself.
+fn leading_closers(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| matches!(c, '}' | ')' | ']'))
+        .count()
+}
+
+/// Formats the lines from `first` to `last` (inclusive) out of the lines of
+/// the whole document and their `scan_lines` info, so that a range is
+/// formatted exactly as it would be within the whole document.
+fn format_range(
+    lines: &[&str],
+    infos: &[LineInfo],
+    first: usize,
+    last: usize,
+) -> Vec<Option<String>> {
+    let mut out = Vec::new();
+
+    let mut previous_blank = match first.checked_sub(1) {
+        None => true,
+        Some(x) => !infos[x].verbatim && lines[x].trim().is_empty(),
+    };
+
+    for (line, info) in lines[first..=last].iter().zip(&infos[first..=last]) {
+        if info.verbatim {
+            out.push(Some(line.to_string()));
+            previous_blank = false;
+            continue;
+        }
+
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            // blank lines at the top or right after another are dropped
+            out.push((!previous_blank).then(String::new));
+            previous_blank = true;
+            continue;
+        }
+
+        let indent = INDENT.repeat(info.depth.saturating_sub(leading_closers(trimmed)));
+
+        out.push(Some(format!("{}{}", indent, trimmed)));
+        previous_blank = false;
+    }
+
+    let formats_blank = |(line, info): (&&str, &LineInfo)| match info.verbatim {
+        true => line.is_empty(),
+        false => line.trim().is_empty(),
+    };
+
+    // blank lines at the end of the document are dropped too
+    if lines[last + 1..]
+        .iter()
+        .zip(&infos[last + 1..])
+        .all(formats_blank)
+    {
+        for line in out.iter_mut().rev() {
+            match line {
+                Some(x) if x.is_empty() => *line = None,
+                None => continue,
+                Some(_) => break,
+            }
+        }
+    }
+
+    out
+}
+
+/// Formats the source line by line, keeping the same number of lines so that
+/// the result can be mapped back to any range of the original. Lines that
+/// should be dropped (eg: repeated blank lines) are returned as `None`.
+///
+/// Only whitespace is ever changed: lines are re-indented by bracket depth,
+/// trailing whitespace is trimmed and runs of blank lines are collapsed.
+pub fn format_lines(text: &str) -> Vec<Option<String>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let infos = scan_lines(text);
+
+    format_range(&lines, &infos, 0, lines.len() - 1)
+}
+
+/// The indentation `line` should have given the brackets around it, even if
+/// it's blank. Lines within comments or strings have none to speak of.
+///
+/// Only the text up to `line` matters, so callers can leave the rest out.
+pub fn line_indent(text: &str, line: usize) -> Option<String> {
+    let info = scan_lines(text).into_iter().nth(line)?;
+
+    if info.verbatim {
+        return None;
+    }
+
+    let closers = leading_closers(text.split('\n').nth(line)?.trim());
+
+    Some(INDENT.repeat(info.depth.saturating_sub(closers)))
+}
+
+/// Removes every span from a serialized AST, leaving only what affects the
+/// meaning of the program.
+fn strip_spans(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("span");
+            map.values_mut().for_each(strip_spans);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_spans),
+        _ => (),
+    }
+}
+
+fn normalized(ast: &Program) -> Option<Value> {
+    let mut value = serde_json::to_value(ast).ok()?;
+    strip_spans(&mut value);
+    Some(value)
+}
+
+/// Returns true if `formatted` parses into the same program as `original`,
+/// spans aside.
+pub fn preserves_semantics(original: &Program, formatted: &str) -> bool {
+    let Ok(formatted) = tx3_lang::parsing::parse_string(formatted) else {
+        return false;
+    };
+
+    match (normalized(original), normalized(&formatted)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Formats a whole document given its parsed `ast`, or returns `None` when
+/// the result wouldn't mean the same thing.
+pub fn format_document(ast: &Program, text: &str) -> Option<String> {
+    let mut formatted = String::new();
+
+    for line in format_lines(text).into_iter().flatten() {
+        formatted.push_str(&line);
+        formatted.push('\n');
+    }
+
+    if !preserves_semantics(ast, &formatted) {
+        return None;
+    }
+
+    Some(formatted)
+}
+
+/// Finds the smallest tx or block that encloses the chars from `start` to
+/// `end`, falling back to any other top-level declaration.
+pub fn enclosing_node(ast: &Program, start: usize, end: usize) -> Option<&Span> {
+    let mut spans: Vec<&Span> = Vec::new();
+
+    spans.extend(ast.parties.iter().map(|x| &x.span));
+    spans.extend(ast.policies.iter().map(|x| &x.span));
+    spans.extend(ast.types.iter().map(|x| &x.span));
+    spans.extend(ast.assets.iter().map(|x| &x.span));
+
+    for tx in &ast.txs {
+        spans.push(&tx.span);
+        spans.extend(tx.references.iter().map(|x| &x.span));
+        spans.extend(tx.inputs.iter().map(|x| &x.span));
+        spans.extend(tx.outputs.iter().map(|x| &x.span));
+        spans.extend(tx.mints.iter().map(|x| &x.span));
+        spans.extend(tx.burns.iter().map(|x| &x.span));
+        spans.extend(tx.collateral.iter().map(|x| &x.span));
+        spans.extend(tx.signers.iter().map(|x| &x.span));
+        spans.extend(tx.validity.iter().map(|x| &x.span));
+        spans.extend(tx.metadata.iter().map(|x| &x.span));
+    }
+
+    spans
+        .into_iter()
+        .filter(|x| x.start <= start && end <= x.end)
+        .min_by_key(|x| x.end - x.start)
+}
+
+/// Formats the lines from `first` to `last` (inclusive) of a document,
+/// returning their new text. Indentation still follows the whole document,
+/// so a block keeps its nesting level.
+pub fn format_line_range(ast: &Program, text: &str, first: usize, last: usize) -> Option<String> {
+    let original: Vec<&str> = text.split('\n').collect();
+
+    if first > last || last >= original.len() {
+        return None;
+    }
+
+    let infos = scan_lines(text);
+
+    let replaced: Vec<String> = format_range(&original, &infos, first, last)
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // rebuild the whole document to make sure the edit is safe in context
+    let mut formatted: Vec<&str> = original[..first].to_vec();
+    formatted.extend(replaced.iter().map(String::as_str));
+    formatted.extend(original.iter().skip(last + 1));
+
+    if !preserves_semantics(ast, &formatted.join("\n")) {
+        return None;
+    }
+
+    Some(replaced.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "party Sender;\n\n\n\ntx transfer(quantity: Int) {\ninput source {\n  from: Sender,   \n/* kept\n   as is */\n}\n}\n\n\n";
+
+    #[test]
+    fn ranges_format_as_within_the_whole_document() {
+        let lines: Vec<&str> = TEXT.split('\n').collect();
+        let infos = scan_lines(TEXT);
+        let whole = format_lines(TEXT);
+
+        for first in 0..lines.len() {
+            for last in first..lines.len() {
+                assert_eq!(
+                    format_range(&lines, &infos, first, last),
+                    whole[first..=last].to_vec(),
+                    "lines {} to {}",
+                    first,
+                    last
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lines_are_indented_by_depth_and_blank_runs_collapsed() {
+        let formatted: Vec<String> = format_lines(TEXT).into_iter().flatten().collect();
+
+        assert_eq!(
+            formatted,
+            vec![
+                "party Sender;",
+                "",
+                "tx transfer(quantity: Int) {",
+                "    input source {",
+                "        from: Sender,",
+                "        /* kept",
+                "   as is */",
+                "    }",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_only_depends_on_the_preceding_text() {
+        let prefix: String = TEXT.split_inclusive('\n').take(7).collect();
+
+        assert_eq!(line_indent(TEXT, 6).as_deref(), Some("        "));
+        assert_eq!(line_indent(&prefix, 6).as_deref(), Some("        "));
+        assert_eq!(line_indent(TEXT, 9).as_deref(), Some("    "));
+        assert_eq!(line_indent(TEXT, 8), None);
+    }
+}
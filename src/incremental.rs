@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Range};
+use tx3_lang::ast::{Program, Span};
+
+use crate::{
+    position_to_offset, span_to_lsp_range, PositionEncoding, CODE_LOWERING_ERROR,
+    CODE_UNDEFINED_PROPERTY_BASE, CODE_UNUSED_DECLARATION,
+};
+
+/// The last analysis of a document, kept so that an edit within a single tx
+/// only needs that tx to be analyzed again.
+#[derive(Debug, Clone)]
+pub struct CachedAnalysis {
+    pub rope: Rope,
+    pub ast: Arc<Program>,
+    /// Whether the txs were lowered, which only happens on a clean analysis.
+    pub lowered: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+fn global_spans(ast: &Program) -> Vec<&Span> {
+    let mut spans: Vec<&Span> = Vec::new();
+
+    spans.extend(ast.parties.iter().map(|x| &x.span));
+    spans.extend(ast.policies.iter().map(|x| &x.span));
+    spans.extend(ast.types.iter().map(|x| &x.span));
+    spans.extend(ast.assets.iter().map(|x| &x.span));
+
+    spans
+}
+
+fn same_text(old_rope: &Rope, old: &Span, new_rope: &Rope, new: &Span) -> bool {
+    fn slice<'a>(rope: &'a Rope, span: &Span) -> ropey::RopeSlice<'a> {
+        let end = span.end.min(rope.len_chars());
+        rope.slice(span.start.min(end)..end)
+    }
+
+    slice(old_rope, old) == slice(new_rope, new)
+}
+
+/// Lists the txs whose source changed between two versions of a document, as
+/// indexes into `new.txs`. Returns `None` when the change goes beyond a single
+/// tx (eg: a top-level declaration was edited, or a tx was added or renamed),
+/// which calls for a full analysis.
+pub fn changed_txs(
+    old_rope: &Rope,
+    old: &Program,
+    new_rope: &Rope,
+    new: &Program,
+) -> Option<Vec<usize>> {
+    let old_globals = global_spans(old);
+    let new_globals = global_spans(new);
+
+    if old_globals.len() != new_globals.len() || old.txs.len() != new.txs.len() {
+        return None;
+    }
+
+    let globals_changed = old_globals
+        .iter()
+        .zip(&new_globals)
+        .any(|(a, b)| !same_text(old_rope, a, new_rope, b));
+
+    if globals_changed {
+        return None;
+    }
+
+    let changed: Vec<usize> = old
+        .txs
+        .iter()
+        .zip(&new.txs)
+        .enumerate()
+        .filter(|(_, (a, b))| !same_text(old_rope, &a.span, new_rope, &b.span))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    if changed.len() > 1 {
+        return None;
+    }
+
+    // a name shared with another tx means duplicate errors spanning both
+    for idx in &changed {
+        let name = &new.txs[*idx].name.value;
+
+        if old.txs[*idx].name.value != *name
+            || new.txs.iter().filter(|x| x.name.value == *name).count() > 1
+        {
+            return None;
+        }
+    }
+
+    Some(changed)
+}
+
+/// Moves a range from an unchanged tx of the cached version to where that tx
+/// now sits. Ranges outside of unchanged txs can't be carried over.
+fn remap_range(
+    cache: &CachedAnalysis,
+    encoding: PositionEncoding,
+    new_rope: &Rope,
+    new: &Program,
+    changed: &[usize],
+    range: &Range,
+) -> Option<Range> {
+    let start = position_to_offset(&cache.rope, encoding, range.start);
+    let end = position_to_offset(&cache.rope, encoding, range.end);
+
+    let (idx, old_tx) = cache
+        .ast
+        .txs
+        .iter()
+        .enumerate()
+        .find(|(_, tx)| tx.span.start <= start && end <= tx.span.end)?;
+
+    if changed.contains(&idx) {
+        return None;
+    }
+
+    let new_start = new.txs.get(idx)?.span.start;
+
+    let span = Span::new(
+        start - old_tx.span.start + new_start,
+        end - old_tx.span.start + new_start,
+    );
+
+    Some(span_to_lsp_range(new_rope, encoding, &span))
+}
+
+/// Diagnostics of the cached analysis that fall within txs left untouched,
+/// moved to the current position of those txs.
+pub fn carry_over(
+    cache: &CachedAnalysis,
+    encoding: PositionEncoding,
+    new_rope: &Rope,
+    new: &Program,
+    changed: &[usize],
+) -> Vec<Diagnostic> {
+    cache
+        .diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let range = remap_range(cache, encoding, new_rope, new, changed, &diagnostic.range)?;
+
+            let related_information = diagnostic.related_information.as_ref().map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let mut item = item.clone();
+                        item.location.range = remap_range(
+                            cache,
+                            encoding,
+                            new_rope,
+                            new,
+                            changed,
+                            &item.location.range,
+                        )?;
+                        Some(item)
+                    })
+                    .collect()
+            });
+
+            Some(Diagnostic {
+                range,
+                related_information,
+                ..diagnostic.clone()
+            })
+        })
+        .collect()
+}
+
+fn has_code(diagnostic: &Diagnostic, code: &str) -> bool {
+    diagnostic.code == Some(NumberOrString::String(code.to_string()))
+}
+
+pub fn is_lowering_diagnostic(diagnostic: &Diagnostic) -> bool {
+    has_code(diagnostic, CODE_LOWERING_ERROR)
+}
+
+/// Returns true for diagnostics reported by the analyzer, as opposed to the
+/// checks the server runs on its own.
+pub fn is_analyzer_diagnostic(diagnostic: &Diagnostic) -> bool {
+    ![
+        CODE_LOWERING_ERROR,
+        CODE_UNDEFINED_PROPERTY_BASE,
+        CODE_UNUSED_DECLARATION,
+    ]
+    .iter()
+    .any(|code| has_code(diagnostic, code))
+}
@@ -0,0 +1,118 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Position, Range};
+use tx3_lang::ast::{DataExpr, OutputBlockField, Program, TxDef};
+
+use crate::ast_to_text::type_to_text;
+use crate::references;
+use crate::visitor::collect_struct_constructors;
+use crate::{data_expr_span, span_to_lsp_range, PositionEncoding};
+
+/// Infers the type of an expression from the AST alone, without running the
+/// analyzer. Parameters can only be resolved when the enclosing tx is known.
+pub fn infer_type(tx: Option<&TxDef>, expr: &DataExpr) -> Option<String> {
+    match expr {
+        DataExpr::Number(_) => Some("Int".to_string()),
+        DataExpr::Bool(_) => Some("Bool".to_string()),
+        DataExpr::HexString(_) => Some("Bytes".to_string()),
+        DataExpr::StructConstructor(x) => Some(x.r#type.value.clone()),
+        DataExpr::FnCall(x) => Some(x.callee.value.clone()),
+        DataExpr::AnyAssetConstructor(_) | DataExpr::MinUtxo(_) => Some("AnyAsset".to_string()),
+        DataExpr::ListConstructor(x) => {
+            let inner = x.elements.first().and_then(|el| infer_type(tx, el))?;
+            Some(format!("List<{}>", inner))
+        }
+        DataExpr::AddOp(x) => infer_type(tx, &x.lhs).or_else(|| infer_type(tx, &x.rhs)),
+        DataExpr::SubOp(x) => infer_type(tx, &x.lhs).or_else(|| infer_type(tx, &x.rhs)),
+        DataExpr::NegateOp(x) => infer_type(tx, &x.operand),
+        DataExpr::Identifier(id) => tx?
+            .parameters
+            .parameters
+            .iter()
+            .find(|param| param.name.value == id.value)
+            .map(|param| type_to_text(&param.r#type)),
+        _ => None,
+    }
+}
+
+/// Constructors already spell out their type, hinting it again is just noise.
+fn is_self_describing(expr: &DataExpr) -> bool {
+    matches!(expr, DataExpr::StructConstructor(_) | DataExpr::FnCall(_))
+}
+
+fn make_hint(position: Position, label: String) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(format!(": {}", label)),
+        kind: Some(InlayHintKind::TYPE),
+        text_edits: None,
+        tooltip: None,
+        padding_left: None,
+        padding_right: None,
+        data: None,
+    }
+}
+
+fn in_range(range: &Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+/// Hints the inferred type of output amounts and datums, and the declared
+/// type of each field in a record constructor.
+pub fn inlay_hints(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    range: &Range,
+) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+
+    for tx in &ast.txs {
+        for output in &tx.outputs {
+            for field in &output.fields {
+                let expr = match field {
+                    OutputBlockField::Amount(x) | OutputBlockField::Datum(x) => x,
+                    OutputBlockField::To(_) => continue,
+                };
+
+                if is_self_describing(expr) {
+                    continue;
+                }
+
+                let (Some(span), Some(ty)) = (data_expr_span(expr), infer_type(Some(tx), expr))
+                else {
+                    continue;
+                };
+
+                let position = span_to_lsp_range(rope, encoding, span).end;
+
+                if in_range(range, position) {
+                    hints.push(make_hint(position, ty));
+                }
+            }
+        }
+    }
+
+    for sc in collect_struct_constructors(ast) {
+        let Some((_, Some(case))) = references::resolve_constructor(ast, sc) else {
+            continue;
+        };
+
+        for field in &sc.case.fields {
+            let Some(declared) = case
+                .fields
+                .iter()
+                .find(|x| x.name.value == field.name.value)
+            else {
+                continue;
+            };
+
+            let position = span_to_lsp_range(rope, encoding, &field.name.span).end;
+
+            if in_range(range, position) {
+                hints.push(make_hint(position, type_to_text(&declared.r#type)));
+            }
+        }
+    }
+
+    hints
+}
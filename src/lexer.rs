@@ -0,0 +1,118 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Comment,
+    Number,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Scans the raw source for constructs the AST doesn't retain. Offsets are
+/// char indices, consistent with `Rope`.
+pub fn scan(text: &str) -> Vec<Token> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('"', _) => {
+                // skip string literals so `//` inside them isn't taken as a comment
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            ('/', Some('/')) => {
+                let start = i;
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Comment,
+                    start,
+                    end: i,
+                });
+            }
+            ('/', Some('*')) => {
+                let start = i;
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(chars.len());
+                tokens.push(Token {
+                    kind: TokenKind::Comment,
+                    start,
+                    end: i,
+                });
+            }
+            (c, _) if c.is_ascii_digit() => {
+                let start = i;
+                let is_word = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+
+                // digits within a name or a `0x` literal, which the AST keeps, aren't numbers
+                let is_number = chars[start..i].iter().all(|c| c.is_ascii_digit());
+
+                if !is_word && is_number {
+                    tokens.push(Token {
+                        kind: TokenKind::Number,
+                        start,
+                        end: i,
+                    });
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments(text: &str) -> Vec<&str> {
+        scan(text)
+            .into_iter()
+            .filter(|x| x.kind == TokenKind::Comment)
+            .map(|x| &text[x.start..x.end])
+            .collect()
+    }
+
+    #[test]
+    fn inline_and_trailing_comments_are_found() {
+        let text = "// the sender\nparty Sender; // trailing\ntype A { /* inline */ x: Int }\n";
+
+        assert_eq!(
+            comments(text),
+            vec!["// the sender", "// trailing", "/* inline */"]
+        );
+    }
+
+    #[test]
+    fn slashes_within_strings_are_not_comments() {
+        let text = "output { datum: \"http://x\", } // after\n";
+
+        assert_eq!(comments(text), vec!["// after"]);
+    }
+
+    #[test]
+    fn block_comments_may_span_lines_or_run_to_the_end() {
+        assert_eq!(comments("/* a\nb */ party A;"), vec!["/* a\nb */"]);
+        assert_eq!(comments("party A; /* open"), vec!["/* open"]);
+    }
+}
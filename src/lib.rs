@@ -1,16 +1,42 @@
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr as _;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use dashmap::DashMap;
 use ropey::Rope;
 use thiserror::Error;
+use tokio::task::JoinHandle;
 use tower_lsp::jsonrpc::ErrorCode;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 
+pub use config::Config;
+
+mod ast_to_dot;
+mod ast_to_mermaid;
 mod ast_to_svg;
+mod ast_to_text;
+mod call_hierarchy;
+mod checks;
 mod cmds;
+mod code_actions;
+mod completion;
+mod config;
+mod diagram;
+mod document_colors;
+mod document_links;
+mod formatting;
+mod incremental;
+mod inlay_hints;
+mod lexer;
+mod references;
+mod schema;
+mod selection_range;
 mod server;
+mod signature_help;
 mod visitor;
+mod workspace;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -26,11 +52,32 @@ pub enum Error {
     #[error("Document not found: {0}")]
     DocumentNotFound(Url),
 
+    #[error("Transaction not found: {0}")]
+    TxNotFound(String),
+
+    #[error("Type not found: {0}")]
+    TypeNotFound(String),
+
+    #[error("Name already in use: {0}")]
+    NameCollision(String),
+
+    #[error("Feature not enabled in this build: {0}")]
+    FeatureNotEnabled(String),
+
+    #[error("Diagram rendering error: {0}")]
+    DiagramRenderError(String),
+
     #[error("Program parsing error: {0}")]
-    ProgramParsingError(#[from] tx3_lang::parsing::Error),
+    ProgramParsingError(tx3_lang::parsing::Error, Range),
+
+    #[error("Program analyzing error: {0}")]
+    ProgramAnalyzingError(#[from] tx3_lang::analyzing::AnalyzeReport),
 
     #[error("Tx3 Lowering error: {0}")]
     TxLoweringError(#[from] tx3_lang::lowering::Error),
+
+    #[error("Command panicked: {0}")]
+    CommandPanicked(String),
 }
 
 impl From<&Error> for ErrorCode {
@@ -39,9 +86,27 @@ impl From<&Error> for ErrorCode {
             Error::InvalidCommand(_) => ErrorCode::InvalidRequest,
             Error::ParseError(_) => ErrorCode::InvalidParams,
             Error::DocumentNotFound(_) => ErrorCode::InvalidParams,
+            Error::TxNotFound(_) => ErrorCode::InvalidParams,
+            Error::TypeNotFound(_) => ErrorCode::InvalidParams,
+            Error::NameCollision(_) => ErrorCode::InvalidParams,
+            Error::FeatureNotEnabled(_) => ErrorCode::InvalidRequest,
+            Error::DiagramRenderError(_) => ErrorCode::InternalError,
             Error::InvalidCommandArgs(_) => ErrorCode::InvalidParams,
-            Error::ProgramParsingError(_) => ErrorCode::InvalidRequest,
+            Error::ProgramParsingError(_, _) => ErrorCode::InvalidRequest,
+            Error::ProgramAnalyzingError(_) => ErrorCode::InvalidRequest,
             Error::TxLoweringError(_) => ErrorCode::InvalidRequest,
+            Error::CommandPanicked(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl Error {
+    /// Extra payload attached to the JSON-RPC error so callers can locate the
+    /// offending construct in the document.
+    fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::ProgramParsingError(_, range) => Some(serde_json::json!({ "range": range })),
+            _ => None,
         }
     }
 }
@@ -51,112 +116,385 @@ impl From<Error> for tower_lsp::jsonrpc::Error {
         tower_lsp::jsonrpc::Error {
             code: From::from(&err),
             message: err.to_string().into(),
-            data: None,
+            data: err.data(),
+        }
+    }
+}
+
+/// How the columns of LSP positions are counted, as negotiated with the client
+/// on `initialize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// Picks UTF-8 when the client offers it, otherwise UTF-16, which every
+    /// client has to support.
+    pub fn negotiate(offered: Option<&[PositionEncodingKind]>) -> Self {
+        match offered {
+            Some(kinds) if kinds.contains(&PositionEncodingKind::UTF8) => Self::Utf8,
+            _ => Self::Utf16,
+        }
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    fn char_width(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
         }
     }
 }
 
-pub fn char_index_to_line_col(rope: &Rope, idx: usize) -> (usize, usize) {
+/// Converts a char offset into a line and a column, counted in the units of
+/// `encoding`.
+pub fn char_index_to_line_col(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    idx: usize,
+) -> (usize, usize) {
+    // spans may point one past the end of the document (eg: errors at EOF)
+    let idx = idx.min(rope.len_chars());
     let line = rope.char_to_line(idx);
     let line_start = rope.line_to_char(line);
-    let col = idx - line_start;
+
+    let col = match encoding {
+        PositionEncoding::Utf8 => rope.char_to_byte(idx) - rope.char_to_byte(line_start),
+        PositionEncoding::Utf16 => rope.char_to_utf16_cu(idx) - rope.char_to_utf16_cu(line_start),
+    };
+
     (line, col)
 }
 
-pub fn position_to_offset(text: &str, position: Position) -> usize {
-    let mut offset = 0;
-    for (line_num, line) in text.lines().enumerate() {
-        if line_num == position.line as usize {
-            offset += position.character.min(line.len() as u32) as usize;
+/// The column at the end of `line`, before its line break.
+pub fn line_end_col(rope: &Rope, encoding: PositionEncoding, line: usize) -> usize {
+    let len = rope
+        .line(line)
+        .chars()
+        .take_while(|c| *c != '\n' && *c != '\r')
+        .count();
+
+    char_index_to_line_col(rope, encoding, rope.line_to_char(line) + len).1
+}
+
+/// Converts an LSP position into a char offset, consistent with
+/// `char_index_to_line_col`. Positions past the end of a line or of the
+/// document are clamped, and columns within a char round down to its start.
+pub fn position_to_offset(rope: &Rope, encoding: PositionEncoding, position: Position) -> usize {
+    let line = position.line as usize;
+
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+
+    let target = position.character as usize;
+    let mut offset = rope.line_to_char(line);
+    let mut col = 0;
+
+    for c in rope.line(line).chars() {
+        if c == '\n' || c == '\r' {
             break;
         }
-        offset += line.len() + 1;
+
+        col += encoding.char_width(c);
+
+        if col > target {
+            break;
+        }
+
+        offset += 1;
     }
+
     offset
 }
 
+/// Applies an edit sent through `didChange`; changes without a range replace
+/// the whole document.
+pub fn apply_content_change(
+    rope: &mut Rope,
+    encoding: PositionEncoding,
+    change: &TextDocumentContentChangeEvent,
+) {
+    match change.range {
+        Some(range) => {
+            let start = position_to_offset(rope, encoding, range.start);
+            let end = position_to_offset(rope, encoding, range.end).max(start);
+
+            rope.remove(start..end);
+            rope.insert(start, &change.text);
+        }
+        None => *rope = Rope::from_str(&change.text),
+    }
+}
+
 pub fn span_contains(span: &tx3_lang::ast::Span, offset: usize) -> bool {
     offset >= span.start && offset < span.end
 }
 
-pub fn span_to_lsp_range(rope: &Rope, loc: &tx3_lang::ast::Span) -> Range {
-    let (start_line, start_col) = char_index_to_line_col(rope, loc.start);
-    let (end_line, end_col) = char_index_to_line_col(rope, loc.end);
+/// The source span of an expression, for the variants that keep one.
+pub fn data_expr_span(expr: &tx3_lang::ast::DataExpr) -> Option<&tx3_lang::ast::Span> {
+    match expr {
+        tx3_lang::ast::DataExpr::String(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::HexString(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::StructConstructor(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::ListConstructor(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::FnCall(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::AnyAssetConstructor(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::Identifier(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::MinUtxo(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::AddOp(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::SubOp(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::NegateOp(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::PropertyOp(x) => Some(&x.span),
+        tx3_lang::ast::DataExpr::UtxoRef(x) => Some(&x.span),
+        _ => None,
+    }
+}
+
+pub fn span_to_lsp_range(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    loc: &tx3_lang::ast::Span,
+) -> Range {
+    let (start_line, start_col) = char_index_to_line_col(rope, encoding, loc.start);
+    let (end_line, end_col) = char_index_to_line_col(rope, encoding, loc.end);
     let start = Position::new(start_line as u32, start_col as u32);
     let end = Position::new(end_line as u32, end_col as u32);
     Range::new(start, end)
 }
 
-fn parse_error_to_diagnostic(rope: &Rope, err: &tx3_lang::parsing::Error) -> Diagnostic {
-    let range = span_to_lsp_range(rope, &err.span);
+/// Collects the `//` comment lines directly above `span`, which document the
+/// declaration that follows them.
+pub fn doc_comment(rope: &Rope, span: &tx3_lang::ast::Span) -> Option<String> {
+    let line = rope.char_to_line(span.start.min(rope.len_chars()));
+
+    let mut lines = Vec::new();
+
+    for line in (0..line).rev() {
+        let text = rope.line(line).to_string();
+
+        let Some(comment) = text.trim().strip_prefix("//") else {
+            break;
+        };
+
+        let comment = comment.trim_start_matches('/');
+        lines.push(comment.strip_prefix(' ').unwrap_or(comment).to_string());
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    lines.reverse();
+
+    Some(lines.join("\n"))
+}
+
+/// Where diagnostic codes are documented, with a section per code.
+const DIAGNOSTICS_DOCS_URL: &str = "https://github.com/tx3-lang/lsp/blob/main/docs/diagnostics.md";
+
+pub(crate) const CODE_PARSE_ERROR: &str = "parse-error";
+pub(crate) const CODE_DUPLICATE_DEFINITION: &str = "duplicate-definition";
+pub(crate) const CODE_NOT_IN_SCOPE: &str = "not-in-scope";
+pub(crate) const CODE_INVALID_SYMBOL: &str = "invalid-symbol";
+pub(crate) const CODE_INVALID_TYPE: &str = "invalid-type";
+pub(crate) const CODE_ANALYSIS_ERROR: &str = "analysis-error";
+pub(crate) const CODE_LOWERING_ERROR: &str = "lowering-error";
+pub(crate) const CODE_UNDEFINED_PROPERTY_BASE: &str = "undefined-property-base";
+pub(crate) const CODE_UNUSED_DECLARATION: &str = "unused-declaration";
+
+/// Maps analyzer errors to their code. Codes are part of the contract with
+/// clients, so they're spelled out here instead of derived from the variant
+/// names, which may change between tx3-lang versions.
+fn analyze_error_code(err: &tx3_lang::analyzing::Error) -> &'static str {
+    use tx3_lang::analyzing::Error;
+
+    match err {
+        Error::DuplicateDefinition(_) => CODE_DUPLICATE_DEFINITION,
+        Error::NotInScope(_) => CODE_NOT_IN_SCOPE,
+        Error::InvalidSymbol(_) => CODE_INVALID_SYMBOL,
+        Error::InvalidTargetType(_) => CODE_INVALID_TYPE,
+        _ => CODE_ANALYSIS_ERROR,
+    }
+}
+
+/// Fills the code of a diagnostic along with the link to its documentation.
+pub(crate) fn with_code(diagnostic: Diagnostic, code: &str) -> Diagnostic {
+    let href = Url::parse(&format!("{}#{}", DIAGNOSTICS_DOCS_URL, code)).ok();
+
+    Diagnostic {
+        code: Some(NumberOrString::String(code.to_string())),
+        code_description: href.map(|href| CodeDescription { href }),
+        ..diagnostic
+    }
+}
+
+/// Widens an empty span to the char at its offset, or to the one before it at
+/// the end of a line or of the document, so editors have something to
+/// underline.
+fn visible_span(rope: &Rope, span: &tx3_lang::ast::Span) -> tx3_lang::ast::Span {
+    if span.start < span.end {
+        return span.clone();
+    }
+
+    let len = rope.len_chars();
+    let start = span.start.min(len);
+    let is_break = |idx: usize| matches!(rope.char(idx), '\n' | '\r');
+
+    if start < len && !is_break(start) {
+        return tx3_lang::ast::Span::new(start, start + 1);
+    }
+
+    if start > 0 && !is_break(start - 1) {
+        return tx3_lang::ast::Span::new(start - 1, start);
+    }
+
+    tx3_lang::ast::Span::new(start, start)
+}
+
+fn parse_error_to_diagnostic(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    err: &tx3_lang::parsing::Error,
+) -> Diagnostic {
+    let range = span_to_lsp_range(rope, encoding, &visible_span(rope, &err.span));
     let message = err.message.clone();
     let source = err.src.clone();
 
-    Diagnostic {
+    let diagnostic = Diagnostic {
         range,
         severity: Some(DiagnosticSeverity::ERROR),
         source: Some(source),
         message,
         ..Default::default()
+    };
+
+    with_code(diagnostic, CODE_PARSE_ERROR)
+}
+
+/// The name and the first two declarations behind a duplicate definition
+/// error. The error itself carries no span, so they're looked up by name.
+fn duplicate_declarations<'a>(
+    ast: &'a tx3_lang::ast::Program,
+    err: &'a tx3_lang::analyzing::Error,
+) -> Option<(&'a str, &'a tx3_lang::ast::Span, &'a tx3_lang::ast::Span)> {
+    let tx3_lang::analyzing::Error::DuplicateDefinition(name) = err else {
+        return None;
+    };
+
+    match references::global_declarations(ast, name).as_slice() {
+        [first, second, ..] => Some((name, *first, *second)),
+        _ => None,
     }
 }
 
-fn analyze_error_to_diagnostic(rope: &Rope, err: &tx3_lang::analyzing::Error) -> Diagnostic {
-    let range = span_to_lsp_range(rope, err.span());
+fn analyze_error_to_diagnostic(
+    rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    ast: &tx3_lang::ast::Program,
+    err: &tx3_lang::analyzing::Error,
+) -> Diagnostic {
+    let duplicate = duplicate_declarations(ast, err);
+
+    // duplicates are reported on the redefinition, pointing back at the original
+    let span = duplicate.map_or(err.span(), |(_, _, second)| second);
+    let range = span_to_lsp_range(rope, encoding, &visible_span(rope, span));
     let message = err.to_string();
     let source = err.src().unwrap_or("tx3").to_string();
 
-    Diagnostic {
+    let related_information = duplicate.map(|(name, first, _)| {
+        vec![DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), span_to_lsp_range(rope, encoding, first)),
+            message: format!("`{}` is first defined here", name),
+        }]
+    });
+
+    let diagnostic = Diagnostic {
         range,
         severity: Some(DiagnosticSeverity::ERROR),
         source: Some(source),
         message,
+        related_information,
         ..Default::default()
-    }
+    };
+
+    with_code(diagnostic, analyze_error_code(err))
 }
 
 fn analyze_report_to_diagnostic(
     rope: &Rope,
+    encoding: PositionEncoding,
+    uri: &Url,
+    ast: &tx3_lang::ast::Program,
     report: &tx3_lang::analyzing::AnalyzeReport,
 ) -> Vec<Diagnostic> {
     report
         .errors
         .iter()
-        .map(|err| analyze_error_to_diagnostic(rope, err))
+        .map(|err| analyze_error_to_diagnostic(rope, encoding, uri, ast, err))
         .collect()
 }
 
-#[derive(Debug)]
+/// Extracts the message from a panic payload, which is a `&str` or a `String`
+/// for the usual `panic!` / `unwrap` cases.
+pub(crate) fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(x) = panic.downcast_ref::<&str>() {
+        return x.to_string();
+    }
+
+    if let Some(x) = panic.downcast_ref::<String>() {
+        return x.clone();
+    }
+
+    "unknown panic".to_string()
+}
+
+fn parse_program(
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Result<tx3_lang::ast::Program, Error> {
+    tx3_lang::parsing::parse_string(document.to_string().as_str()).map_err(|err| {
+        let range = span_to_lsp_range(document, encoding, &err.span);
+        Error::ProgramParsingError(err, range)
+    })
+}
+
+#[derive(Debug, Clone)]
 pub struct Context {
     pub client: Client,
-    pub documents: DashMap<Url, Rope>,
-    //asts: DashMap<Url, tx3_lang::ast::Program>,
+    pub documents: Arc<DashMap<Url, Rope>>,
+    pub asts: Arc<DashMap<Url, Arc<tx3_lang::ast::Program>>>,
+    pending: Arc<DashMap<Url, JoinHandle<()>>>,
+    /// How positions are encoded, as negotiated with this client.
+    position_encoding: Arc<RwLock<PositionEncoding>>,
+    /// The last analysis of each open document, to re-analyze only what edits
+    /// touch.
+    analyses: Arc<DashMap<Url, incremental::CachedAnalysis>>,
+    config: Arc<RwLock<Config>>,
+    /// `.tx3` files on disk, including those that aren't open.
+    workspace: Arc<DashMap<Url, workspace::IndexedFile>>,
+    workspace_roots: Arc<RwLock<Vec<Url>>>,
 }
 
 impl Context {
-    fn is_type_field_reference(
-        ast: &tx3_lang::ast::Program,
-        identifier: &str,
-        offset: usize,
-    ) -> bool {
-        for type_def in &ast.types {
-            if crate::span_contains(&type_def.span, offset) {
-                for case in &type_def.cases {
-                    for field in &case.fields {
-                        if identifier == field.r#type.to_string() {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        false
-    }
+    /// Collects the semantic tokens of a document. When `range` is given (as
+    /// char offsets), tokens entirely outside of it are skipped.
     fn collect_semantic_tokens(
         &self,
         ast: &tx3_lang::ast::Program,
         rope: &Rope,
+        range: Option<(usize, usize)>,
     ) -> Vec<SemanticToken> {
+        let encoding = self.encoding();
         const TOKEN_TYPE: u32 = 0;
         const TOKEN_PARAMETER: u32 = 1;
         const TOKEN_VARIABLE: u32 = 2;
@@ -164,11 +502,15 @@ impl Context {
         const TOKEN_PARTY: u32 = 4;
         const TOKEN_POLICY: u32 = 5;
         const TOKEN_FUNCTION: u32 = 6;
-        // const TOKEN_KEYWORD: u32 = 7;
-        // const TOKEN_PROPERTY: u32 = 8;
+        const TOKEN_COMMENT: u32 = 7;
+        const TOKEN_STRING: u32 = 8;
+        const TOKEN_NUMBER: u32 = 9;
+        // const TOKEN_KEYWORD: u32 = 10;
+        // const TOKEN_PROPERTY: u32 = 11;
 
         const MOD_DECLARATION: u32 = 1 << 0;
         const MOD_DEFINITION: u32 = 1 << 1;
+        const MOD_INVALID: u32 = 1 << 4;
 
         #[derive(Debug, Clone)]
         struct TokenInfo {
@@ -179,81 +521,179 @@ impl Context {
 
         let mut token_infos: Vec<TokenInfo> = Vec::new();
         let text = rope.to_string();
+        let index = crate::visitor::ProgramIndex::new(ast);
+
+        let in_range = |start: usize, end: usize| match range {
+            Some((range_start, range_end)) => start < range_end && end > range_start,
+            None => true,
+        };
+
+        // top-level names, resolved in order of precedence
+        let mut globals: HashMap<&str, u32> = HashMap::new();
+        let parties = ast.parties.iter().map(|x| (&x.name, TOKEN_PARTY));
+        let policies = ast.policies.iter().map(|x| (&x.name, TOKEN_POLICY));
+        let types = ast.types.iter().map(|x| (&x.name, TOKEN_TYPE));
+        for (name, token_type) in parties.chain(policies).chain(types) {
+            globals.entry(name.value.as_str()).or_insert(token_type);
+        }
+
+        let assets: HashSet<&str> = ast.assets.iter().map(|x| x.name.value.as_str()).collect();
+
+        let mut txs: HashMap<&str, usize> = HashMap::new();
+        for (index, tx) in ast.txs.iter().enumerate() {
+            txs.entry(tx.name.value.as_str()).or_insert(index);
+        }
+
+        // references that don't resolve to any declaration, only when the
+        // client opted into flagging them
+        let mut undefined: HashSet<(usize, usize)> = HashSet::new();
+
+        if self.config().highlight_undefined_symbols {
+            for occurrence in &index.occurrences {
+                if occurrence.role != crate::visitor::Role::Reference {
+                    continue;
+                }
+
+                let name = &occurrence.identifier.value;
+
+                let resolved = match occurrence.tx {
+                    Some(tx) => references::is_declared(ast, tx, name),
+                    None => references::global_declaration(ast, name).is_some(),
+                };
+
+                if !resolved {
+                    let span = &occurrence.identifier.span;
+                    undefined.insert((span.start, span.end));
+                }
+            }
+        }
+
+        let mut processed_spans = HashSet::new();
+
+        for symbol in &index.symbols {
+            let identifier = symbol.identifier;
+            let name = identifier.value.as_str();
+
+            if !in_range(identifier.span.start, identifier.span.end) {
+                continue;
+            }
+
+            if !processed_spans.insert((identifier.span.start, identifier.span.end)) {
+                continue;
+            }
+
+            // names matching a field type of the enclosing type are types, even
+            // if they aren't declared
+            let is_field_type = symbol.type_def.is_some_and(|ty| {
+                ty.cases
+                    .iter()
+                    .flat_map(|case| &case.fields)
+                    .any(|field| field.r#type.to_string() == name)
+            });
+
+            // a tx name wins over a parameter unless the parameter's tx comes first
+            let param_tx = symbol
+                .tx
+                .filter(|(_, tx)| {
+                    tx.parameters
+                        .parameters
+                        .iter()
+                        .any(|p| p.name.value == name)
+                })
+                .map(|(index, _)| index);
+
+            let token_type = if let Some(token_type) = globals.get(name) {
+                *token_type
+            } else if is_field_type {
+                TOKEN_TYPE
+            } else if assets.contains(name) {
+                TOKEN_CLASS
+            } else {
+                match (txs.get(name), param_tx) {
+                    (Some(tx), Some(param)) if param < *tx => TOKEN_PARAMETER,
+                    (Some(_), _) => TOKEN_FUNCTION,
+                    (None, Some(_)) => TOKEN_PARAMETER,
+                    (None, None) => TOKEN_VARIABLE,
+                }
+            };
 
-        let mut processed_spans = std::collections::HashSet::new();
-
-        for offset in 0..text.len() {
-            if let Some(symbol) = crate::visitor::find_symbol_in_program(ast, offset) {
-                match symbol {
-                    crate::visitor::SymbolAtOffset::Identifier(identifier) => {
-                        // Skip if we've already processed this exact span
-                        let span_key = (identifier.span.start, identifier.span.end);
-                        if processed_spans.contains(&span_key) {
-                            continue;
-                        }
-                        processed_spans.insert(span_key);
-
-                        let token_type = if ast
-                            .parties
-                            .iter()
-                            .any(|p| p.name.value == identifier.value)
-                        {
-                            TOKEN_PARTY
-                        } else if ast
-                            .policies
-                            .iter()
-                            .any(|p| p.name.value == identifier.value)
-                        {
-                            TOKEN_POLICY
-                        } else if ast.types.iter().any(|t| t.name.value == identifier.value) {
-                            TOKEN_TYPE
-                        } else if Context::is_type_field_reference(ast, &identifier.value, offset) {
-                            TOKEN_TYPE
-                        } else if ast.assets.iter().any(|a| a.name.value == identifier.value) {
-                            TOKEN_CLASS
-                        } else {
-                            let mut found_type = None;
-
-                            for tx in &ast.txs {
-                                if tx.name.value == identifier.value {
-                                    found_type = Some(TOKEN_FUNCTION);
-                                    break;
-                                }
-
-                                if crate::span_contains(&tx.span, offset) {
-                                    for param in &tx.parameters.parameters {
-                                        if param.name.value == identifier.value {
-                                            found_type = Some(TOKEN_PARAMETER);
-                                            break;
-                                        }
-                                    }
-                                }
-
-                                if found_type.is_some() {
-                                    break;
-                                }
-                            }
-                            found_type.unwrap_or(TOKEN_VARIABLE)
-                        };
-
-                        token_infos.push(TokenInfo {
-                            range: crate::span_to_lsp_range(rope, &identifier.span),
-                            token_type,
-                            token_modifiers: MOD_DECLARATION | MOD_DEFINITION,
-                        });
-                    }
-                    visitor::SymbolAtOffset::TypeIdentifier(_x) => {
-                        // TODO: wait for the introduction of `TypeAnnotation` in AST
-
-                        // token_infos.push(TokenInfo {
-                        //     range: crate::span_to_lsp_range(rope, &x.span),
-                        //     token_type: TOKEN_TYPE,
-                        //     token_modifiers: MOD_DECLARATION | MOD_DEFINITION,
-                        // });
-                    }
+            let mut token_modifiers = MOD_DECLARATION | MOD_DEFINITION;
+
+            if undefined.contains(&(identifier.span.start, identifier.span.end)) {
+                token_modifiers |= MOD_INVALID;
+            }
+
+            token_infos.push(TokenInfo {
+                range: crate::span_to_lsp_range(rope, encoding, &identifier.span),
+                token_type,
+                token_modifiers,
+            });
+        }
+
+        let literals = &index.literals;
+
+        for literal in literals
+            .iter()
+            .filter(|x| in_range(x.span.start, x.span.end))
+        {
+            let token_type = match literal.kind {
+                crate::visitor::LiteralKind::String => TOKEN_STRING,
+                crate::visitor::LiteralKind::Bytes => TOKEN_NUMBER,
+            };
+
+            token_infos.push(TokenInfo {
+                range: crate::span_to_lsp_range(rope, encoding, literal.span),
+                token_type,
+                token_modifiers: 0,
+            });
+        }
+
+        for token in crate::lexer::scan(&text) {
+            if !in_range(token.start, token.end) {
+                continue;
+            }
+
+            if token.kind == crate::lexer::TokenKind::Number {
+                // eg: the index of a utxo ref, already covered by the literal
+                let in_literal = literals
+                    .iter()
+                    .any(|x| x.span.start <= token.start && token.end <= x.span.end);
+
+                if !in_literal {
+                    token_infos.push(TokenInfo {
+                        range: crate::span_to_lsp_range(
+                            rope,
+                            encoding,
+                            &tx3_lang::ast::Span::new(token.start, token.end),
+                        ),
+                        token_type: TOKEN_NUMBER,
+                        token_modifiers: 0,
+                    });
                 }
+
+                continue;
+            }
+
+            // tokens can't span multiple lines, so block comments are split per line
+            let (start_line, start_col) = char_index_to_line_col(rope, encoding, token.start);
+            let (end_line, end_col) = char_index_to_line_col(rope, encoding, token.end);
+
+            for line in start_line..=end_line {
+                let line_len = line_end_col(rope, encoding, line);
+                let start = if line == start_line { start_col } else { 0 };
+                let end = if line == end_line { end_col } else { line_len };
+
+                token_infos.push(TokenInfo {
+                    range: Range::new(
+                        Position::new(line as u32, start as u32),
+                        Position::new(line as u32, end as u32),
+                    ),
+                    token_type: TOKEN_COMMENT,
+                    token_modifiers: 0,
+                });
             }
         }
+
         token_infos.sort_by(|a, b| match a.range.start.line.cmp(&b.range.start.line) {
             std::cmp::Ordering::Equal => a.range.start.character.cmp(&b.range.start.character),
             other => other,
@@ -299,7 +739,14 @@ impl Context {
     pub fn new_for_client(client: Client) -> Self {
         Self {
             client,
-            documents: DashMap::new(),
+            documents: Default::default(),
+            asts: Default::default(),
+            pending: Default::default(),
+            position_encoding: Arc::new(RwLock::new(PositionEncoding::Utf16)),
+            analyses: Default::default(),
+            config: Default::default(),
+            workspace: Default::default(),
+            workspace_roots: Default::default(),
         }
     }
 
@@ -314,23 +761,686 @@ impl Context {
         Ok(document.value().clone())
     }
 
+    /// Returns the AST of a document, reusing the one cached by
+    /// `process_document` and parsing on the spot if there's none.
+    fn get_document_ast(&self, uri: &Url, rope: &Rope) -> Option<Arc<tx3_lang::ast::Program>> {
+        if let Some(ast) = self.asts.get(uri) {
+            return Some(ast.value().clone());
+        }
+
+        tx3_lang::parsing::parse_string(rope.to_string().as_str())
+            .ok()
+            .map(Arc::new)
+    }
+
+    /// Returns the AST of a document, falling back to the one of its last
+    /// version that parsed, whose spans may lag behind `rope` by an edit.
+    fn last_parsed_ast(&self, uri: &Url, rope: &Rope) -> Option<Arc<tx3_lang::ast::Program>> {
+        if let Some(ast) = self.asts.get(uri) {
+            return Some(ast.value().clone());
+        }
+
+        if let Some(cache) = self.analyses.get(uri) {
+            return Some(cache.ast.clone());
+        }
+
+        self.get_document_ast(uri, rope)
+    }
+
+    /// Looks for a top-level declaration named `name` in the workspace files
+    /// other than `uri`, for protocols split across several files.
+    fn find_in_other_documents(&self, uri: &Url, name: &str) -> Option<Location> {
+        self.workspace_files()
+            .into_iter()
+            .filter(|(other, _)| other != uri)
+            .find_map(|(other, file)| {
+                let span = references::global_declaration(&file.ast, name)?;
+
+                Some(Location {
+                    uri: other.clone(),
+                    range: span_to_lsp_range(&file.rope, self.encoding(), span),
+                })
+            })
+    }
+
     fn get_document_program(&self, url_arg: &str) -> Result<tx3_lang::ast::Program, Error> {
         let document = self.get_document(url_arg)?;
-        tx3_lang::parsing::parse_string(document.to_string().as_str()).map_err(Error::ProgramParsingError)
+        parse_program(&document, self.encoding())
+    }
+
+    /// Same as `get_document_program`, but parses `text` instead when the
+    /// document isn't open, for clients that have the source at hand without
+    /// an editor buffer.
+    fn get_document_program_or_text(
+        &self,
+        url_arg: &str,
+        text: Option<&str>,
+    ) -> Result<tx3_lang::ast::Program, Error> {
+        match (self.get_document_program(url_arg), text) {
+            (Err(Error::DocumentNotFound(_)), Some(text)) => {
+                parse_program(&Rope::from_str(text), self.encoding())
+            }
+            (result, _) => result,
+        }
     }
 
     async fn process_document(&self, uri: Url, text: &str) -> Vec<Diagnostic> {
         let rope = Rope::from_str(text);
-        self.documents.insert(uri.clone(), rope.clone());
+        self.store_document(&uri, rope.clone());
+        self.analyze_document(&uri, &rope)
+    }
+
+    fn store_document(&self, uri: &Url, rope: Rope) {
+        self.documents.insert(uri.clone(), rope);
+
+        // whatever was cached belongs to the previous version of the text
+        self.asts.remove(uri);
+    }
 
-        let ast = tx3_lang::parsing::parse_string(text);
+    /// Analyzes the whole program, returning its diagnostics and whether the
+    /// txs got lowered.
+    fn analyze_fully(
+        &self,
+        rope: &Rope,
+        uri: &Url,
+        mut ast: tx3_lang::ast::Program,
+    ) -> (Vec<Diagnostic>, bool) {
+        let analysis = tx3_lang::analyzing::analyze(&mut ast);
+        let mut diagnostics =
+            analyze_report_to_diagnostic(rope, self.encoding(), uri, &ast, &analysis);
+
+        // lowering relies on a clean analysis
+        let lowered = analysis.errors.is_empty() && self.config().enable_lowering_diagnostics;
+
+        if lowered {
+            diagnostics.extend(checks::lowering_errors(&ast, rope, self.encoding()));
+        }
+
+        diagnostics.extend(checks::undefined_property_bases(
+            &ast,
+            rope,
+            self.encoding(),
+        ));
+
+        (diagnostics, lowered)
+    }
+
+    /// Analyzes only the tx an edit touched, along with the top-level
+    /// declarations, reusing the cached diagnostics of the other txs. Returns
+    /// `None` when the edit goes beyond a single tx.
+    fn analyze_incrementally(
+        &self,
+        rope: &Rope,
+        uri: &Url,
+        ast: &tx3_lang::ast::Program,
+    ) -> Option<(Vec<Diagnostic>, bool)> {
+        let cache = self.analyses.get(uri)?.value().clone();
+
+        let changed = incremental::changed_txs(&cache.rope, &cache.ast, rope, ast)?;
+        let mut carried = incremental::carry_over(&cache, self.encoding(), rope, ast, &changed);
+
+        let mut partial = ast.clone();
+        partial.txs = changed.iter().map(|idx| ast.txs[*idx].clone()).collect();
+
+        let analysis = tx3_lang::analyzing::analyze(&mut partial);
+        let mut diagnostics =
+            analyze_report_to_diagnostic(rope, self.encoding(), uri, &partial, &analysis);
+
+        let clean =
+            analysis.errors.is_empty() && !carried.iter().any(incremental::is_analyzer_diagnostic);
+        let lowered = clean && self.config().enable_lowering_diagnostics;
+
+        if lowered && !cache.lowered {
+            // the untouched txs were never lowered, so there's nothing to reuse
+            return None;
+        }
+
+        if lowered {
+            diagnostics.extend(checks::lowering_errors(&partial, rope, self.encoding()));
+        } else {
+            carried.retain(|x| !incremental::is_lowering_diagnostic(x));
+        }
+
+        diagnostics.extend(checks::undefined_property_bases(
+            &partial,
+            rope,
+            self.encoding(),
+        ));
+        diagnostics.extend(carried);
+
+        Some((diagnostics, lowered))
+    }
+
+    fn analyze_document(&self, uri: &Url, rope: &Rope) -> Vec<Diagnostic> {
+        let text = rope.to_string();
+
+        // a freshly created file has nothing to report yet
+        if text.trim().is_empty() {
+            self.analyses.remove(uri);
+            return vec![];
+        }
+
+        let ast = tx3_lang::parsing::parse_string(&text);
 
         match ast {
-            Ok(mut ast) => {
-                let analysis = tx3_lang::analyzing::analyze(&mut ast);
-                analyze_report_to_diagnostic(&rope, &analysis)
+            Ok(ast) => {
+                let ast = Arc::new(ast);
+
+                // holding the document while caching keeps a newer text from
+                // being stored in between, which would leave a stale AST behind
+                let current = self.documents.get(uri);
+                let is_current = current.as_ref().is_some_and(|x| x.value() == rope);
+                if is_current {
+                    self.asts.insert(uri.clone(), ast.clone());
+                }
+                drop(current);
+
+                let (mut diagnostics, lowered) = match self.analyze_incrementally(rope, uri, &ast) {
+                    Some(x) => x,
+                    None => self.analyze_fully(rope, uri, ast.as_ref().clone()),
+                };
+
+                // only open documents get edited, anything else is analyzed once
+                if is_current {
+                    self.analyses.insert(
+                        uri.clone(),
+                        incremental::CachedAnalysis {
+                            rope: rope.clone(),
+                            ast: ast.clone(),
+                            lowered,
+                            diagnostics: diagnostics.clone(),
+                        },
+                    );
+                }
+
+                if self.config().warn_unused_declarations {
+                    diagnostics.extend(checks::unused_declarations(&ast, rope, self.encoding()));
+                }
+
+                diagnostics
             }
-            Err(e) => vec![parse_error_to_diagnostic(&rope, &e)],
+            Err(e) => vec![parse_error_to_diagnostic(rope, self.encoding(), &e)],
+        }
+    }
+
+    /// Computes the same diagnostics as `analyze_document` from scratch,
+    /// without reading nor updating any of the cached ASTs and analyses.
+    fn diagnose_document(&self, uri: &Url, rope: &Rope) -> Vec<Diagnostic> {
+        let text = rope.to_string();
+
+        if text.trim().is_empty() {
+            return vec![];
         }
+
+        match tx3_lang::parsing::parse_string(&text) {
+            Ok(ast) => {
+                let (mut diagnostics, _) = self.analyze_fully(rope, uri, ast.clone());
+
+                if self.config().warn_unused_declarations {
+                    diagnostics.extend(checks::unused_declarations(&ast, rope, self.encoding()));
+                }
+
+                diagnostics
+            }
+            Err(e) => vec![parse_error_to_diagnostic(rope, self.encoding(), &e)],
+        }
+    }
+
+    fn is_current(&self, uri: &Url, rope: &Rope) -> bool {
+        self.documents.get(uri).is_some_and(|x| x.value() == rope)
+    }
+
+    fn encoding(&self) -> PositionEncoding {
+        *self.position_encoding.read().unwrap()
+    }
+
+    fn set_encoding(&self, encoding: PositionEncoding) {
+        *self.position_encoding.write().unwrap() = encoding;
+    }
+
+    fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    fn set_config(&self, config: Config) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Stores the new text right away but only analyzes it once the document
+    /// has been quiet for a while, replacing any analysis still pending.
+    fn schedule_analysis(&self, uri: Url, rope: Rope, version: i32) {
+        self.store_document(&uri, rope.clone());
+
+        let context = self.clone();
+        let delay = Duration::from_millis(self.config().debounce_ms);
+        let key = uri.clone();
+
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            let diagnostics = context.analyze_document(&uri, &rope);
+
+            // a newer change will publish its own diagnostics
+            if !context.is_current(&uri, &rope) {
+                return;
+            }
+
+            context
+                .client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+        });
+
+        if let Some(previous) = self.pending.insert(key, task) {
+            previous.abort();
+        }
+    }
+
+    fn cancel_analysis(&self, uri: &Url) {
+        if let Some((_, task)) = self.pending.remove(uri) {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "let 𝄞 = \"😀\";\nparty 😀x;\n";
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    #[test]
+    fn astral_chars_take_two_utf16_units_and_four_bytes() {
+        let rope = Rope::from_str(TEXT);
+
+        // the space right after the clef
+        let idx = 5;
+
+        assert_eq!(
+            char_index_to_line_col(&rope, PositionEncoding::Utf16, idx),
+            (0, 6)
+        );
+        assert_eq!(
+            char_index_to_line_col(&rope, PositionEncoding::Utf8, idx),
+            (0, 8)
+        );
+
+        // the `x` after the emoji on the second line
+        let idx = rope.line_to_char(1) + 7;
+
+        assert_eq!(
+            char_index_to_line_col(&rope, PositionEncoding::Utf16, idx),
+            (1, 8)
+        );
+        assert_eq!(
+            char_index_to_line_col(&rope, PositionEncoding::Utf8, idx),
+            (1, 10)
+        );
+    }
+
+    #[test]
+    fn positions_round_trip_in_both_encodings() {
+        let rope = Rope::from_str(TEXT);
+
+        for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16] {
+            for idx in 0..=rope.len_chars() {
+                let (line, col) = char_index_to_line_col(&rope, encoding, idx);
+                let position = Position::new(line as u32, col as u32);
+
+                assert_eq!(
+                    position_to_offset(&rope, encoding, position),
+                    idx,
+                    "{:?} at {}",
+                    encoding,
+                    idx
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn columns_within_an_astral_char_round_down() {
+        let rope = Rope::from_str(TEXT);
+
+        // between the two UTF-16 units of the clef
+        let position = Position::new(0, 5);
+        assert_eq!(
+            position_to_offset(&rope, PositionEncoding::Utf16, position),
+            4
+        );
+
+        // within the four UTF-8 bytes of the clef
+        for col in 5..8 {
+            let position = Position::new(0, col);
+            assert_eq!(
+                position_to_offset(&rope, PositionEncoding::Utf8, position),
+                4
+            );
+        }
+    }
+
+    #[test]
+    fn line_end_col_counts_in_the_encoding() {
+        let rope = Rope::from_str(TEXT);
+
+        assert_eq!(line_end_col(&rope, PositionEncoding::Utf16, 1), 10);
+        assert_eq!(line_end_col(&rope, PositionEncoding::Utf8, 1), 12);
+    }
+
+    #[test]
+    fn content_changes_replace_astral_chars() {
+        let cases = [
+            (
+                PositionEncoding::Utf16,
+                range((0, 4), (0, 6)),
+                range((1, 6), (1, 8)),
+            ),
+            (
+                PositionEncoding::Utf8,
+                range((0, 4), (0, 8)),
+                range((1, 6), (1, 10)),
+            ),
+        ];
+
+        for (encoding, clef, emoji) in cases {
+            let mut rope = Rope::from_str(TEXT);
+
+            // replace the emoji first so the range of the clef stays valid
+            let change = TextDocumentContentChangeEvent {
+                range: Some(emoji),
+                range_length: None,
+                text: "y".to_string(),
+            };
+            apply_content_change(&mut rope, encoding, &change);
+
+            let change = TextDocumentContentChangeEvent {
+                range: Some(clef),
+                range_length: None,
+                text: "x".to_string(),
+            };
+            apply_content_change(&mut rope, encoding, &change);
+
+            assert_eq!(rope.to_string(), "let x = \"😀\";\nparty yx;\n");
+        }
+    }
+
+    #[test]
+    fn utf8_is_picked_only_when_offered() {
+        assert_eq!(PositionEncoding::negotiate(None), PositionEncoding::Utf16);
+
+        let offered = [PositionEncodingKind::UTF16, PositionEncodingKind::UTF8];
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&offered)),
+            PositionEncoding::Utf8
+        );
+
+        let offered = [PositionEncodingKind::UTF32];
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&offered)),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn parsing_errors_carry_their_range_to_the_client() {
+        let rope = Rope::from_str("party Sender;\nparty 42;\n");
+
+        let Err(err) = parse_program(&rope, PositionEncoding::Utf16) else {
+            panic!("the document shouldn't parse");
+        };
+
+        let Error::ProgramParsingError(_, range) = &err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(range.start, Position::new(1, 6));
+
+        let expected = serde_json::json!({ "range": range });
+        let err = tower_lsp::jsonrpc::Error::from(err);
+        assert_eq!(err.data, Some(expected));
+    }
+
+    #[test]
+    fn comments_get_semantic_tokens() {
+        let text = "// the sender\nparty Sender; // trailing\n";
+        let rope = Rope::from_str(text);
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+
+        let (service, _) = tower_lsp::LspService::new(Context::new_for_client);
+        let tokens = service.inner().collect_semantic_tokens(&ast, &rope, None);
+
+        // back to absolute positions, as `(line, col, length)`
+        let mut comments = Vec::new();
+        let (mut line, mut col) = (0, 0);
+
+        for token in tokens {
+            line += token.delta_line;
+            col = if token.delta_line == 0 {
+                col + token.delta_start
+            } else {
+                token.delta_start
+            };
+
+            if token.token_type == 7 {
+                comments.push((line, col, token.length));
+            }
+        }
+
+        assert_eq!(comments, vec![(0, 0, 13), (1, 14, 11)]);
+    }
+
+    #[test]
+    fn empty_documents_map_to_the_origin() {
+        let rope = Rope::from_str("");
+
+        for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16] {
+            assert_eq!(char_index_to_line_col(&rope, encoding, 0), (0, 0));
+            // eg: an error at EOF, one past the end
+            assert_eq!(char_index_to_line_col(&rope, encoding, 1), (0, 0));
+            assert_eq!(position_to_offset(&rope, encoding, Position::new(0, 0)), 0);
+            assert_eq!(position_to_offset(&rope, encoding, Position::new(3, 7)), 0);
+        }
+    }
+
+    #[test]
+    fn comment_lines_right_above_a_declaration_document_it() {
+        let text = "party Sender;\n// stray\n\n/// The one paying.\n// Usually a wallet.\nparty Receiver;\n";
+        let rope = Rope::from_str(text);
+
+        let span_of = |needle: &str| {
+            let start = text.find(needle).unwrap();
+            tx3_lang::ast::Span::new(start, start + needle.len())
+        };
+
+        assert_eq!(
+            doc_comment(&rope, &span_of("party Receiver;")).as_deref(),
+            Some("The one paying.\nUsually a wallet.")
+        );
+        assert_eq!(doc_comment(&rope, &span_of("party Sender;")), None);
+    }
+
+    #[test]
+    fn accented_chars_take_one_utf16_unit_and_two_bytes() {
+        let rope = Rope::from_str("// señal 😀\nparty Niño;\n");
+
+        // the `o` after the `ñ`
+        let idx = rope.line_to_char(1) + 9;
+
+        assert_eq!(
+            position_to_offset(&rope, PositionEncoding::Utf16, Position::new(1, 9)),
+            idx
+        );
+        assert_eq!(
+            position_to_offset(&rope, PositionEncoding::Utf8, Position::new(1, 10)),
+            idx
+        );
+        assert_eq!(
+            char_index_to_line_col(&rope, PositionEncoding::Utf8, idx),
+            (1, 10)
+        );
+
+        // right after the emoji, past both the accent and the astral char
+        assert_eq!(
+            position_to_offset(&rope, PositionEncoding::Utf16, Position::new(0, 11)),
+            10
+        );
+        assert_eq!(
+            position_to_offset(&rope, PositionEncoding::Utf8, Position::new(0, 14)),
+            10
+        );
+    }
+
+    #[test]
+    fn incremental_edits_match_the_full_text() {
+        let edit = |range: Option<Range>, text: &str| TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        };
+
+        let changes = [
+            edit(Some(range((0, 7), (0, 7))), "lice"),
+            edit(Some(range((1, 0), (1, 0))), "party Bob;\n"),
+            edit(Some(range((0, 6), (0, 11))), "Carol"),
+            edit(Some(range((1, 6), (2, 0))), "Dave;\n"),
+        ];
+
+        for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16] {
+            let mut rope = Rope::from_str("party A;\n");
+
+            for change in &changes {
+                apply_content_change(&mut rope, encoding, change);
+            }
+
+            assert_eq!(rope.to_string(), "party Carol;\nparty Dave;\n");
+
+            // a change without a range replaces the whole text
+            apply_content_change(&mut rope, encoding, &edit(None, "party Eve;\n"));
+            assert_eq!(rope.to_string(), "party Eve;\n");
+        }
+    }
+
+    #[test]
+    fn empty_spans_are_widened_within_the_document() {
+        let span = |start, end| tx3_lang::ast::Span::new(start, end);
+        let widen = |text: &str, start, end| {
+            let widened = visible_span(&Rope::from_str(text), &span(start, end));
+            (widened.start, widened.end)
+        };
+
+        assert_eq!(widen("party A;", 3, 3), (3, 4));
+        assert_eq!(widen("party A;", 0, 5), (0, 5));
+        // at the end of the document or of a line, the char before is used
+        assert_eq!(widen("party A", 7, 7), (6, 7));
+        assert_eq!(widen("party A\nparty B;", 7, 7), (6, 7));
+        assert_eq!(widen("ab", 100, 100), (1, 2));
+        // nothing to underline at all
+        assert_eq!(widen("", 0, 0), (0, 0));
+        assert_eq!(widen("a\n\nb", 2, 2), (2, 2));
+    }
+
+    #[test]
+    fn errors_at_eof_get_a_visible_range() {
+        let rope = Rope::from_str("party Sender");
+        let err = tx3_lang::parsing::parse_string(&rope.to_string()).unwrap_err();
+
+        let diagnostic = parse_error_to_diagnostic(&rope, PositionEncoding::Utf16, &err);
+
+        assert!(diagnostic.range.start < diagnostic.range.end);
+        assert!(diagnostic.range.end <= Position::new(0, 12));
+    }
+
+    #[test]
+    fn duplicates_point_back_at_the_first_declaration() {
+        let text = "party Sender;\nparty Sender;\n";
+        let rope = Rope::from_str(text);
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+        let uri = Url::parse("file:///main.tx3").unwrap();
+        let err = tx3_lang::analyzing::Error::DuplicateDefinition("Sender".to_string());
+
+        let diagnostic =
+            analyze_error_to_diagnostic(&rope, PositionEncoding::Utf16, &uri, &ast, &err);
+
+        assert_eq!(diagnostic.range.start.line, 1);
+
+        let related = diagnostic.related_information.unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.range.start.line, 0);
+    }
+
+    #[test]
+    fn range_requests_add_up_to_the_whole_document() {
+        let text = r#"// parties
+party Sender;
+party Receiver;
+
+type Order {
+    amount: Int,
+    owner: Bytes,
+}
+
+tx transfer(quantity: Int, order: Order) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: Receiver,
+        amount: Ada(quantity),
+        datum: Order { amount: quantity, owner: "abc" },
+    }
+    output {
+        to: Sender,
+        amount: source - Ada(quantity),
+    }
+}
+"#;
+        let rope = Rope::from_str(text);
+        let ast = tx3_lang::parsing::parse_string(text).unwrap();
+
+        let (service, _) = tower_lsp::LspService::new(Context::new_for_client);
+        let context = service.inner();
+
+        // back to absolute positions, as `(line, col, length, type, modifiers)`
+        let absolute = |tokens: Vec<SemanticToken>| {
+            let (mut line, mut col) = (0, 0);
+
+            tokens
+                .into_iter()
+                .map(|token| {
+                    line += token.delta_line;
+                    col = if token.delta_line == 0 {
+                        col + token.delta_start
+                    } else {
+                        token.delta_start
+                    };
+
+                    (
+                        line,
+                        col,
+                        token.length,
+                        token.token_type,
+                        token.token_modifiers_bitset,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let whole = absolute(context.collect_semantic_tokens(&ast, &rope, None));
+
+        let mut per_line = Vec::new();
+
+        for line in 0..rope.len_lines() {
+            let start = rope.line_to_char(line);
+            let end = start + rope.line(line).len_chars();
+            let tokens = context.collect_semantic_tokens(&ast, &rope, Some((start, end)));
+            per_line.extend(absolute(tokens));
+        }
+
+        assert!(!whole.is_empty());
+        assert_eq!(per_line, whole);
     }
 }
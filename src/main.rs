@@ -1,4 +1,6 @@
 use clap::Parser;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_lsp::{LspService, Server};
 use tx3_lsp::Context;
@@ -8,16 +10,19 @@ use tx3_lsp::Context;
 struct Args {
     #[arg(short, long)]
     stdio: bool,
-}
-
-#[tokio::main]
-async fn main() {
-    let _args = Args::parse();
 
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
+    /// Listen for a client on this TCP port instead of using stdio. Clients are
+    /// served one at a time; the next connection is accepted once the current
+    /// one closes.
+    #[arg(short, long)]
+    port: Option<u16>,
+}
 
-    let (service, socket) = LspService::new(Context::new_for_client);
+async fn serve(input: impl AsyncRead + Unpin, output: impl AsyncWrite) {
+    let (service, socket) = LspService::build(Context::new_for_client)
+        .custom_method("$/tx3/commands", Context::commands)
+        .custom_method("$/tx3/resolveType", Context::resolve_type)
+        .finish();
 
     // Create a logging middleware
     let service = ServiceBuilder::new()
@@ -25,7 +30,34 @@ async fn main() {
         .map_response(|response| response)
         .service(service);
 
-    let server = Server::new(stdin, stdout, socket);
+    let server = Server::new(input, output, socket);
 
     server.serve(service).await;
 }
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+
+    let Some(port) = args.port else {
+        serve(tokio::io::stdin(), tokio::io::stdout()).await;
+        return Ok(());
+    };
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+
+    loop {
+        // a failed connection shouldn't take down the server for the next ones
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                eprintln!("failed to accept connection: {}", err);
+                continue;
+            }
+        };
+
+        let (read, write) = tokio::io::split(stream);
+
+        serve(read, write).await;
+    }
+}
@@ -0,0 +1,351 @@
+use tx3_lang::ast::{
+    DataExpr, Identifier, OutputBlockField, Program, Span, StructConstructor, TxDef, Type, TypeDef,
+    VariantCase,
+};
+
+use crate::visitor::{
+    collect_occurrences, find_symbol_in_program, Occurrence, Role, SymbolAtOffset,
+};
+use crate::{data_expr_span, span_contains};
+
+/// Where a name is declared: either at the top level of the program or as a
+/// parameter / named output local to a single tx.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    Global,
+    Tx(&'a TxDef),
+}
+
+fn tx_local_names(tx: &TxDef) -> impl Iterator<Item = &str> {
+    let params = tx
+        .parameters
+        .parameters
+        .iter()
+        .map(|p| p.name.value.as_str());
+    let inputs = tx.inputs.iter().map(|i| i.name.as_str());
+    let references = tx.references.iter().map(|r| r.name.as_str());
+    let outputs = tx
+        .outputs
+        .iter()
+        .filter_map(|o| o.name.as_ref())
+        .map(|n| n.value.as_str());
+
+    params.chain(inputs).chain(references).chain(outputs)
+}
+
+fn global_names(ast: &Program) -> impl Iterator<Item = &str> {
+    let parties = ast.parties.iter().map(|x| x.name.value.as_str());
+    let policies = ast.policies.iter().map(|x| x.name.value.as_str());
+    let types = ast.types.iter().map(|x| x.name.value.as_str());
+    let assets = ast.assets.iter().map(|x| x.name.value.as_str());
+    let txs = ast.txs.iter().map(|x| x.name.value.as_str());
+
+    parties
+        .chain(policies)
+        .chain(types)
+        .chain(assets)
+        .chain(txs)
+}
+
+fn declares_locally(tx: &TxDef, name: &str) -> bool {
+    tx_local_names(tx).any(|x| x == name)
+}
+
+/// Finds the span of the top-level declaration named `name`, if any.
+pub fn global_declaration<'a>(ast: &'a Program, name: &str) -> Option<&'a Span> {
+    global_declarations(ast, name).into_iter().next()
+}
+
+/// Collects the spans of every top-level declaration named `name`; there's
+/// more than one when the name is defined twice.
+pub fn global_declarations<'a>(ast: &'a Program, name: &str) -> Vec<&'a Span> {
+    let parties = ast.parties.iter().map(|x| (&x.name, &x.span));
+    let policies = ast.policies.iter().map(|x| (&x.name, &x.span));
+    let types = ast.types.iter().map(|x| (&x.name, &x.span));
+    let assets = ast.assets.iter().map(|x| (&x.name, &x.span));
+    let txs = ast.txs.iter().map(|x| (&x.name, &x.span));
+
+    parties
+        .chain(policies)
+        .chain(types)
+        .chain(assets)
+        .chain(txs)
+        .filter(|(x, _)| x.value == name)
+        .map(|(_, span)| span)
+        .collect()
+}
+
+/// Returns true if `name` resolves to a declaration visible from within `tx`.
+pub fn is_declared(ast: &Program, tx: &TxDef, name: &str) -> bool {
+    declares_locally(tx, name) || global_names(ast).any(|x| x == name)
+}
+
+/// Resolves the scope of `name` as seen from `offset`; tx-local declarations
+/// shadow top-level ones.
+pub fn resolve_scope<'a>(ast: &'a Program, name: &str, offset: usize) -> Scope<'a> {
+    for tx in &ast.txs {
+        if span_contains(&tx.span, offset) && declares_locally(tx, name) {
+            return Scope::Tx(tx);
+        }
+    }
+
+    Scope::Global
+}
+
+/// Returns true if declaring `name` in `scope` would clash with an existing
+/// declaration.
+pub fn is_name_taken(ast: &Program, name: &str, scope: Scope) -> bool {
+    match scope {
+        Scope::Global => global_names(ast).any(|x| x == name),
+        Scope::Tx(tx) => declares_locally(tx, name),
+    }
+}
+
+/// Returns true if renaming `name` (declared in `scope`) to `new_name` would
+/// clash with an existing declaration or change what another identifier
+/// resolves to through shadowing.
+pub fn is_rename_conflict(ast: &Program, name: &str, new_name: &str, scope: Scope) -> bool {
+    if is_name_taken(ast, new_name, scope) {
+        return true;
+    }
+
+    match scope {
+        // the renamed local would shadow uses of a top-level `new_name` within the tx
+        Scope::Tx(tx) => find_occurrences(ast, new_name, Scope::Global)
+            .iter()
+            .any(|x| x.tx.is_some_and(|t| std::ptr::eq(t, tx))),
+        // a tx-local `new_name` would capture uses of the renamed symbol
+        Scope::Global => find_occurrences(ast, name, Scope::Global)
+            .iter()
+            .any(|x| x.tx.is_some_and(|t| declares_locally(t, new_name))),
+    }
+}
+
+/// Keeps the declaration and every use of `name` within `scope`.
+fn filter_occurrences<'a>(
+    occurrences: Vec<Occurrence<'a>>,
+    name: &str,
+    scope: Scope,
+) -> Vec<Occurrence<'a>> {
+    occurrences
+        .into_iter()
+        .filter(|x| x.role != Role::Member && x.identifier.value == name)
+        .filter(|x| match (scope, x.tx) {
+            (Scope::Global, None) => true,
+            (Scope::Global, Some(tx)) => !declares_locally(tx, name),
+            (Scope::Tx(scope_tx), Some(tx)) => std::ptr::eq(scope_tx, tx),
+            (Scope::Tx(_), None) => false,
+        })
+        .collect()
+}
+
+/// Collects the declaration and every use of `name` within `scope`.
+pub fn find_occurrences<'a>(ast: &'a Program, name: &str, scope: Scope) -> Vec<Occurrence<'a>> {
+    filter_occurrences(collect_occurrences(ast), name, scope)
+}
+
+/// Same as `symbol_at`, checking for members against occurrences that were
+/// already collected.
+fn symbol_within<'a>(
+    ast: &'a Program,
+    occurrences: &[Occurrence],
+    offset: usize,
+) -> Option<&'a Identifier> {
+    let identifier = match find_symbol_in_program(ast, offset)? {
+        SymbolAtOffset::Identifier(x) => x,
+        SymbolAtOffset::TypeIdentifier(Type::Custom(x)) => x,
+        SymbolAtOffset::TypeIdentifier(_) => return None,
+    };
+
+    let is_member = occurrences.iter().any(|x| {
+        // compare bounds, Span's equality holds whenever either side is a dummy
+        x.role == Role::Member
+            && x.identifier.span.start == identifier.span.start
+            && x.identifier.span.end == identifier.span.end
+    });
+
+    if is_member {
+        return None;
+    }
+
+    Some(identifier)
+}
+
+/// Resolves the identifier under the cursor, skipping record fields and
+/// variant cases which belong to their type rather than to a scope.
+pub fn symbol_at(ast: &Program, offset: usize) -> Option<&Identifier> {
+    symbol_within(ast, &collect_occurrences(ast), offset)
+}
+
+/// Resolves the symbol under the cursor along with its scope and every
+/// occurrence of it, declaration included. The program is walked only once
+/// for all of them.
+pub fn resolve_at(
+    ast: &Program,
+    offset: usize,
+) -> Option<(&Identifier, Scope<'_>, Vec<Occurrence<'_>>)> {
+    let occurrences = collect_occurrences(ast);
+
+    let identifier = symbol_within(ast, &occurrences, offset)?;
+    let scope = resolve_scope(ast, &identifier.value, offset);
+    let occurrences = filter_occurrences(occurrences, &identifier.value, scope);
+
+    Some((identifier, scope, occurrences))
+}
+
+/// Finds where the symbol under the cursor is declared. That's the name for
+/// most symbols, but inputs and references don't keep a span for their name so
+/// their whole block is used instead.
+pub fn declaration_at(ast: &Program, offset: usize) -> Option<&Span> {
+    let (identifier, scope, occurrences) = resolve_at(ast, offset)?;
+
+    if let Some(x) = occurrences.iter().find(|x| x.role == Role::Declaration) {
+        return Some(&x.identifier.span);
+    }
+
+    let Scope::Tx(tx) = scope else {
+        return None;
+    };
+
+    let input = tx.inputs.iter().find(|x| x.name == identifier.value);
+    let reference = tx.references.iter().find(|x| x.name == identifier.value);
+
+    input
+        .map(|x| &x.span)
+        .or_else(|| reference.map(|x| &x.span))
+}
+
+/// Resolves the type a struct constructor builds, along with the variant case
+/// it selects. Records have a single implicit case, which is picked whenever
+/// the constructor doesn't name a matching one.
+pub fn resolve_constructor<'a>(
+    ast: &'a Program,
+    sc: &StructConstructor,
+) -> Option<(&'a TypeDef, Option<&'a VariantCase>)> {
+    let ty = ast.types.iter().find(|x| x.name.value == sc.r#type.value)?;
+
+    let case = ty
+        .cases
+        .iter()
+        .find(|x| x.name.value == sc.case.name.value)
+        .or_else(|| ty.cases.first().filter(|_| ty.cases.len() == 1));
+
+    Some((ty, case))
+}
+
+/// The custom type a type annotation refers to, looking through lists and
+/// maps (where the value type wins over the key).
+fn custom_type_name(ty: &Type) -> Option<&str> {
+    match ty {
+        Type::Custom(x) => Some(&x.value),
+        Type::List(inner) => custom_type_name(inner),
+        Type::Map(key, value) => custom_type_name(value).or_else(|| custom_type_name(key)),
+        _ => None,
+    }
+}
+
+fn expr_type_name<'a>(tx: &'a TxDef, expr: &'a DataExpr) -> Option<&'a str> {
+    match expr {
+        DataExpr::StructConstructor(x) => Some(&x.r#type.value),
+        DataExpr::ListConstructor(x) => x.elements.first().and_then(|el| expr_type_name(tx, el)),
+        DataExpr::Identifier(id) => tx
+            .parameters
+            .parameters
+            .iter()
+            .find(|param| param.name.value == id.value)
+            .and_then(|param| custom_type_name(&param.r#type)),
+        _ => None,
+    }
+}
+
+/// Resolves the type of the value under the cursor: the declared type of a
+/// parameter, the type named by a type identifier, or the type of the datum
+/// of the output surrounding the cursor. Primitive types have no definition.
+pub fn type_definition_at(ast: &Program, offset: usize) -> Option<&TypeDef> {
+    let find_type = |name: &str| ast.types.iter().find(|x| x.name.value == name);
+    let tx = ast.txs.iter().find(|tx| span_contains(&tx.span, offset));
+
+    if let Some(identifier) = symbol_at(ast, offset) {
+        let param = tx.and_then(|tx| {
+            tx.parameters
+                .parameters
+                .iter()
+                .find(|param| param.name.value == identifier.value)
+        });
+
+        // parameters shadow types of the same name
+        if let Some(param) = param {
+            return find_type(custom_type_name(&param.r#type)?);
+        }
+
+        if let Some(ty) = find_type(&identifier.value) {
+            return Some(ty);
+        }
+    }
+
+    let tx = tx?;
+
+    tx.outputs
+        .iter()
+        .flat_map(|x| &x.fields)
+        .find_map(|field| match field {
+            OutputBlockField::Datum(expr)
+                if data_expr_span(expr).is_some_and(|x| span_contains(x, offset)) =>
+            {
+                expr_type_name(tx, expr)
+            }
+            _ => None,
+        })
+        .and_then(find_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"party Sender;
+
+type Order {
+    amount: Int,
+}
+
+tx pay(order: Order, orders: List<Order>, quantity: Int) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity),
+    }
+    output {
+        to: Sender,
+        amount: Ada(quantity),
+        datum: order,
+    }
+    output {
+        to: Sender,
+        amount: Ada(quantity),
+        datum: orders,
+    }
+}
+"#;
+
+    fn type_at(needle: &str) -> Option<String> {
+        let ast = tx3_lang::parsing::parse_string(TEXT).unwrap();
+        let offset = TEXT.find(needle).unwrap();
+
+        type_definition_at(&ast, offset).map(|x| x.name.value.clone())
+    }
+
+    #[test]
+    fn custom_typed_params_lead_to_their_type() {
+        assert_eq!(type_at("order,\n").as_deref(), Some("Order"));
+    }
+
+    #[test]
+    fn list_params_lead_to_the_type_of_their_items() {
+        assert_eq!(type_at("orders,\n").as_deref(), Some("Order"));
+    }
+
+    #[test]
+    fn primitive_params_have_no_type_definition() {
+        assert_eq!(type_at("quantity)"), None);
+    }
+}
@@ -0,0 +1,153 @@
+use serde_json::{json, Value};
+use tx3_lang::ast::{Program, Type, TypeDef};
+
+use crate::ast_to_text::type_to_text;
+
+fn type_def_to_schema(ast: &Program, type_def: &TypeDef, visiting: &mut Vec<String>) -> Value {
+    visiting.push(type_def.name.value.clone());
+
+    let cases: Vec<Value> = type_def
+        .cases
+        .iter()
+        .map(|case| {
+            let fields: Vec<Value> = case
+                .fields
+                .iter()
+                .map(|field| {
+                    json!({
+                        "name": field.name.value,
+                        "type": type_to_schema(ast, &field.r#type, visiting),
+                    })
+                })
+                .collect();
+
+            json!({
+                "name": case.name.value,
+                "fields": fields,
+            })
+        })
+        .collect();
+
+    visiting.pop();
+
+    json!({
+        "name": type_def.name.value,
+        "cases": cases,
+    })
+}
+
+fn type_to_schema(ast: &Program, ty: &Type, visiting: &mut Vec<String>) -> Value {
+    match ty {
+        Type::List(inner) => json!({
+            "kind": "List",
+            "of": type_to_schema(ast, inner, visiting),
+        }),
+        Type::Custom(id) if visiting.contains(&id.value) => json!({
+            "kind": "Custom",
+            "name": id.value,
+            "cycle": true,
+        }),
+        Type::Custom(id) => match ast.types.iter().find(|x| x.name.value == id.value) {
+            Some(type_def) => {
+                let mut schema = type_def_to_schema(ast, type_def, visiting);
+                schema["kind"] = json!("Custom");
+                schema
+            }
+            None => json!({
+                "kind": "Custom",
+                "name": id.value,
+                "unresolved": true,
+            }),
+        },
+        other => json!({ "kind": type_to_text(other) }),
+    }
+}
+
+/// Expands the type named `name` into its full field tree, recursively
+/// resolving custom field types. Recursive references are marked with
+/// `"cycle": true` instead of being expanded again.
+pub fn resolve_type(ast: &Program, name: &str) -> Option<Value> {
+    let type_def = ast.types.iter().find(|x| x.name.value == name)?;
+
+    Some(type_def_to_schema(ast, type_def, &mut Vec::new()))
+}
+
+/// The cases of `type_def` with each field's type as Tx3 source text, without
+/// expanding custom field types.
+fn shallow_cases(type_def: &TypeDef) -> Vec<Value> {
+    type_def
+        .cases
+        .iter()
+        .map(|case| {
+            let fields: Vec<Value> = case
+                .fields
+                .iter()
+                .map(|field| {
+                    json!({
+                        "name": field.name.value,
+                        "type": type_to_text(&field.r#type),
+                    })
+                })
+                .collect();
+
+            json!({
+                "name": case.name.value,
+                "fields": fields,
+            })
+        })
+        .collect()
+}
+
+/// Describes the type named `name`: its cases and fields, with the custom
+/// types of its fields (or of their list items) expanded one level deep.
+pub fn describe_type(ast: &Program, name: &str) -> Option<Value> {
+    let type_def = ast.types.iter().find(|x| x.name.value == name)?;
+
+    let cases: Vec<Value> = type_def
+        .cases
+        .iter()
+        .map(|case| {
+            let fields: Vec<Value> = case
+                .fields
+                .iter()
+                .map(|field| {
+                    let mut ty = &field.r#type;
+
+                    while let Type::List(inner) = ty {
+                        ty = inner;
+                    }
+
+                    let definition = match ty {
+                        Type::Custom(id) => ast
+                            .types
+                            .iter()
+                            .find(|x| x.name.value == id.value)
+                            .map(|x| json!({ "name": x.name.value, "cases": shallow_cases(x) })),
+                        _ => None,
+                    };
+
+                    let mut value = json!({
+                        "name": field.name.value,
+                        "type": type_to_text(&field.r#type),
+                    });
+
+                    if let Some(definition) = definition {
+                        value["definition"] = definition;
+                    }
+
+                    value
+                })
+                .collect();
+
+            json!({
+                "name": case.name.value,
+                "fields": fields,
+            })
+        })
+        .collect();
+
+    Some(json!({
+        "name": type_def.name.value,
+        "cases": cases,
+    }))
+}
@@ -0,0 +1,317 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range, SelectionRange};
+use tx3_lang::ast::{
+    CollateralBlockField, DataExpr, Identifier, InputBlockField, MintBlockField, OutputBlockField,
+    PolicyField, PolicyValue, Program, Span, TxDef, TypeDef, ValidityBlockField,
+};
+
+use crate::{data_expr_span, span_contains, span_to_lsp_range, PositionEncoding};
+
+/// Collects the spans of every node enclosing an offset, walking down from
+/// the program to the innermost expression.
+struct SpanCollector<'a> {
+    offset: usize,
+    spans: Vec<&'a Span>,
+}
+
+impl<'a> SpanCollector<'a> {
+    /// Records `span` if it encloses the offset. Returns whether it does, so
+    /// callers can skip the children of nodes that don't.
+    fn enter(&mut self, span: &'a Span) -> bool {
+        let inside = span_contains(span, self.offset);
+
+        if inside {
+            self.spans.push(span);
+        }
+
+        inside
+    }
+
+    fn visit_identifier(&mut self, id: &'a Identifier) {
+        self.enter(&id.span);
+    }
+
+    fn visit_data_expr(&mut self, expr: &'a DataExpr) {
+        if let Some(span) = data_expr_span(expr) {
+            if !self.enter(span) {
+                return;
+            }
+        }
+
+        match expr {
+            DataExpr::StructConstructor(x) => {
+                self.visit_identifier(&x.r#type);
+
+                if !self.enter(&x.case.span) {
+                    return;
+                }
+
+                self.visit_identifier(&x.case.name);
+
+                for field in &x.case.fields {
+                    if self.enter(&field.span) {
+                        self.visit_identifier(&field.name);
+                        self.visit_data_expr(&field.value);
+                    }
+                }
+
+                if let Some(spread) = &x.case.spread {
+                    self.visit_data_expr(spread);
+                }
+            }
+            DataExpr::ListConstructor(x) => {
+                for element in &x.elements {
+                    self.visit_data_expr(element);
+                }
+            }
+            DataExpr::FnCall(x) => {
+                self.visit_identifier(&x.callee);
+                for arg in &x.args {
+                    self.visit_data_expr(arg);
+                }
+            }
+            DataExpr::AnyAssetConstructor(x) => {
+                self.visit_data_expr(&x.policy);
+                self.visit_data_expr(&x.asset_name);
+                self.visit_data_expr(&x.amount);
+            }
+            DataExpr::AddOp(x) => {
+                self.visit_data_expr(&x.lhs);
+                self.visit_data_expr(&x.rhs);
+            }
+            DataExpr::SubOp(x) => {
+                self.visit_data_expr(&x.lhs);
+                self.visit_data_expr(&x.rhs);
+            }
+            DataExpr::NegateOp(x) => self.visit_data_expr(&x.operand),
+            DataExpr::PropertyOp(x) => {
+                self.visit_data_expr(&x.operand);
+                self.visit_data_expr(&x.property);
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_type_def(&mut self, ty: &'a TypeDef) {
+        if !self.enter(&ty.span) {
+            return;
+        }
+
+        self.visit_identifier(&ty.name);
+
+        for case in &ty.cases {
+            if !self.enter(&case.span) {
+                continue;
+            }
+
+            self.visit_identifier(&case.name);
+
+            for field in &case.fields {
+                if self.enter(&field.span) {
+                    self.visit_identifier(&field.name);
+                }
+            }
+        }
+    }
+
+    fn visit_tx(&mut self, tx: &'a TxDef) {
+        if !self.enter(&tx.span) {
+            return;
+        }
+
+        self.visit_identifier(&tx.name);
+
+        if self.enter(&tx.parameters.span) {
+            for param in &tx.parameters.parameters {
+                self.visit_identifier(&param.name);
+            }
+        }
+
+        for block in &tx.references {
+            if self.enter(&block.span) {
+                self.visit_data_expr(&block.r#ref);
+            }
+        }
+
+        for block in &tx.inputs {
+            if !self.enter(&block.span) {
+                continue;
+            }
+
+            for field in &block.fields {
+                match field {
+                    InputBlockField::From(x)
+                    | InputBlockField::MinAmount(x)
+                    | InputBlockField::Redeemer(x)
+                    | InputBlockField::Ref(x) => self.visit_data_expr(x),
+                    InputBlockField::DatumIs(_) => (),
+                }
+            }
+        }
+
+        for block in &tx.outputs {
+            if !self.enter(&block.span) {
+                continue;
+            }
+
+            if let Some(name) = &block.name {
+                self.visit_identifier(name);
+            }
+
+            for field in &block.fields {
+                match field {
+                    OutputBlockField::To(x)
+                    | OutputBlockField::Amount(x)
+                    | OutputBlockField::Datum(x) => self.visit_data_expr(x),
+                }
+            }
+        }
+
+        for block in tx.mints.iter().chain(&tx.burns) {
+            if !self.enter(&block.span) {
+                continue;
+            }
+
+            for field in &block.fields {
+                match field {
+                    MintBlockField::Amount(x) | MintBlockField::Redeemer(x) => {
+                        self.visit_data_expr(x)
+                    }
+                }
+            }
+        }
+
+        for block in &tx.collateral {
+            if !self.enter(&block.span) {
+                continue;
+            }
+
+            for field in &block.fields {
+                match field {
+                    CollateralBlockField::From(x)
+                    | CollateralBlockField::MinAmount(x)
+                    | CollateralBlockField::Ref(x) => self.visit_data_expr(x),
+                }
+            }
+        }
+
+        if let Some(block) = &tx.validity {
+            if self.enter(&block.span) {
+                for field in &block.fields {
+                    match field {
+                        ValidityBlockField::SinceSlot(x) | ValidityBlockField::UntilSlot(x) => {
+                            self.visit_data_expr(x)
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(block) = &tx.signers {
+            if self.enter(&block.span) {
+                for signer in &block.signers {
+                    self.visit_data_expr(signer);
+                }
+            }
+        }
+
+        if let Some(block) = &tx.metadata {
+            if self.enter(&block.span) {
+                for field in &block.fields {
+                    if self.enter(&field.span) {
+                        self.visit_data_expr(&field.key);
+                        self.visit_data_expr(&field.value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_program(&mut self, ast: &'a Program) {
+        self.enter(&ast.span);
+
+        for party in &ast.parties {
+            if self.enter(&party.span) {
+                self.visit_identifier(&party.name);
+            }
+        }
+
+        for policy in &ast.policies {
+            if !self.enter(&policy.span) {
+                continue;
+            }
+
+            self.visit_identifier(&policy.name);
+
+            match &policy.value {
+                PolicyValue::Assign(x) => {
+                    self.enter(&x.span);
+                }
+                PolicyValue::Constructor(x) => {
+                    if self.enter(&x.span) {
+                        for field in &x.fields {
+                            match field {
+                                PolicyField::Hash(x)
+                                | PolicyField::Script(x)
+                                | PolicyField::Ref(x) => self.visit_data_expr(x),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for asset in &ast.assets {
+            if self.enter(&asset.span) {
+                self.visit_identifier(&asset.name);
+                self.visit_data_expr(&asset.policy);
+                self.visit_data_expr(&asset.asset_name);
+            }
+        }
+
+        for ty in &ast.types {
+            self.visit_type_def(ty);
+        }
+
+        for tx in &ast.txs {
+            self.visit_tx(tx);
+        }
+    }
+}
+
+/// Builds the chain of ranges an "expand selection" goes through from
+/// `position`, each one enclosing the previous.
+pub fn selection_range_at(
+    ast: &Program,
+    rope: &Rope,
+    encoding: PositionEncoding,
+    position: Position,
+    offset: usize,
+) -> SelectionRange {
+    let mut collector = SpanCollector {
+        offset,
+        spans: Vec::new(),
+    };
+
+    collector.visit_program(ast);
+
+    // outermost first, so that each range becomes the parent of the next one
+    let mut spans = collector.spans;
+    spans.sort_by_key(|x| (x.start, std::cmp::Reverse(x.end)));
+    spans.dedup_by_key(|x| (x.start, x.end));
+
+    let mut selection: Option<SelectionRange> = None;
+
+    for span in spans {
+        selection = Some(SelectionRange {
+            range: span_to_lsp_range(rope, encoding, span),
+            parent: selection.map(Box::new),
+        });
+    }
+
+    // the spec requires a range for every position, even outside any node
+    selection.unwrap_or(SelectionRange {
+        range: Range::new(position, position),
+        parent: None,
+    })
+}
@@ -1,32 +1,180 @@
-use serde_json::Value;
-use tower_lsp::{jsonrpc::Result, lsp_types::*, LanguageServer};
-use tx3_lang::ast::Identifier;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 
+use futures::FutureExt;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tower_lsp::{
+    jsonrpc::Result,
+    lsp_types::{
+        request::{
+            GotoDeclarationParams, GotoDeclarationResponse, GotoImplementationParams,
+            GotoImplementationResponse, GotoTypeDefinitionParams, GotoTypeDefinitionResponse,
+        },
+        *,
+    },
+    LanguageServer,
+};
+use tx3_lang::ast::{
+    CollateralBlockField, Identifier, InputBlockField, MintBlockField, OutputBlockField,
+    PolicyValue, Span, ValidityBlockField,
+};
+
+use crate::ast_to_text::{
+    asset_expr_to_text, data_expr_to_text, policy_value_to_text, type_to_text,
+};
 use crate::{
-    cmds, position_to_offset, span_contains, span_to_lsp_range,
-    visitor::{find_symbol_in_program, SymbolAtOffset},
-    Context,
+    apply_content_change, call_hierarchy, char_index_to_line_col, cmds, code_actions, completion,
+    data_expr_span, doc_comment, document_colors, document_links, formatting, inlay_hints,
+    line_end_col, panic_message, position_to_offset, references, schema, selection_range,
+    signature_help, span_contains, span_to_lsp_range,
+    visitor::{collect_struct_constructors, find_symbol_in_program, Role, SymbolAtOffset},
+    workspace::is_tx3_file,
+    Config, Context, Error, PositionEncoding,
 };
 
+/// Semantic tokens sent per `$/progress` notification when the client asks
+/// for partial results.
+const SEMANTIC_TOKENS_CHUNK: usize = 1000;
+
+/// `$/progress` carrying a chunk of semantic tokens as a partial result;
+/// `lsp_types` only models it for work done progress.
+enum SemanticTokensProgress {}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SemanticTokensProgressParams {
+    token: ProgressToken,
+    value: SemanticTokensPartialResult,
+}
+
+impl notification::Notification for SemanticTokensProgress {
+    type Params = SemanticTokensProgressParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+fn with_doc_comment(value: String, rope: &Rope, span: &Span) -> String {
+    match doc_comment(rope, span) {
+        Some(doc) => format!("{}\n\n{}", value, doc),
+        None => value,
+    }
+}
+
+// the outline only has room for a single line of documentation
+fn symbol_detail(kind: &str, rope: &Rope, span: &Span) -> String {
+    match doc_comment(rope, span) {
+        Some(doc) => format!("{}: {}", kind, doc.lines().next().unwrap_or_default()),
+        None => kind.to_string(),
+    }
+}
+
+fn markdown_hover(value: String, rope: &Rope, encoding: PositionEncoding, span: &Span) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(span_to_lsp_range(rope, encoding, span)),
+    }
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for Context {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let (config, rejected) =
+            Config::from_initialization_options(params.initialization_options.as_ref());
+
+        self.set_config(config);
+
+        if !rejected.is_empty() {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!(
+                        "ignoring malformed initialization options, using defaults for: {}",
+                        rejected.join(", ")
+                    ),
+                )
+                .await;
+        }
+
+        #[allow(deprecated)]
+        let roots = match params.workspace_folders {
+            Some(folders) => folders.into_iter().map(|x| x.uri).collect(),
+            None => params.root_uri.into_iter().collect(),
+        };
+
+        *self.workspace_roots.write().unwrap() = roots;
+
+        let encoding = PositionEncoding::negotiate(
+            params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|x| x.position_encodings.as_deref()),
+        );
+
+        self.set_encoding(encoding);
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
+                position_encoding: Some(encoding.kind()),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
-                completion_provider: Some(Default::default()),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![
+                        ":".to_string(),
+                        "{".to_string(),
+                        ".".to_string(),
+                    ]),
+                    resolve_provider: Some(true),
+                    ..Default::default()
+                }),
                 definition_provider: Some(OneOf::Left(true)),
                 type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                color_provider: Some(ColorProviderCapability::Simple(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "}".to_string(),
+                    more_trigger_character: Some(vec!["\n".to_string()]),
+                }),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["{".to_string(), ",".to_string()]),
+                    ..Default::default()
+                }),
                 declaration_provider: Some(DeclarationCapability::Simple(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
-                            work_done_progress_options: WorkDoneProgressOptions::default(),
+                            // progress and partial results are reported when
+                            // the request carries a token for them
+                            work_done_progress_options: WorkDoneProgressOptions {
+                                work_done_progress: Some(true),
+                            },
                             legend: SemanticTokensLegend {
                                 token_types: vec![
                                     SemanticTokenType::TYPE,
@@ -36,6 +184,9 @@ impl LanguageServer for Context {
                                     SemanticTokenType::new("party"),
                                     SemanticTokenType::new("policy"),
                                     SemanticTokenType::FUNCTION,
+                                    SemanticTokenType::COMMENT,
+                                    SemanticTokenType::STRING,
+                                    SemanticTokenType::NUMBER,
                                     // SemanticTokenType::KEYWORD,
                                     // SemanticTokenType::PROPERTY,
                                 ],
@@ -44,6 +195,7 @@ impl LanguageServer for Context {
                                     SemanticTokenModifier::DEFINITION,
                                     SemanticTokenModifier::READONLY,
                                     SemanticTokenModifier::STATIC,
+                                    SemanticTokenModifier::new("invalid"),
                                 ],
                             },
                             range: Some(true),
@@ -52,7 +204,10 @@ impl LanguageServer for Context {
                     ),
                 ),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec!["generate-tir".to_string(), "generate-ast".to_string()],
+                    commands: cmds::COMMANDS
+                        .iter()
+                        .map(|cmd| cmd.name.to_string())
+                        .collect(),
                     work_done_progress_options: WorkDoneProgressOptions {
                         work_done_progress: None,
                     },
@@ -70,11 +225,133 @@ impl LanguageServer for Context {
         self.client
             .log_message(MessageType::INFO, "server initialized!")
             .await;
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.tx3".to_string()),
+                kind: None,
+            }],
+        };
+
+        let registration = Registration {
+            id: "tx3-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("couldn't watch workspace files: {}", err),
+                )
+                .await;
+        }
+
+        // indexing a large workspace shouldn't hold other requests back
+        let context = self.clone();
+        tokio::spawn(async move { context.index_workspace().await });
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let items = self
+                .last_parsed_ast(uri, document.value())
+                .and_then(|ast| completion::property_items(&ast, document.value(), offset));
+
+            if let Some(items) = items {
+                return Ok(Some(CompletionResponse::Array(items)));
+            }
+
+            // keywords are still useful while the statement being typed doesn't parse
+            let mut items = match self.get_document_ast(uri, document.value()) {
+                Some(ast) => completion::completions_at(&ast, document.value(), offset),
+                None => completion::keyword_items(document.value(), offset),
+            };
+
+            // resolving deferred items needs to know which document they're from
+            for item in &mut items {
+                if let Some(Value::Object(data)) = &mut item.data {
+                    data.insert("uri".to_string(), json!(uri.to_string()));
+                }
+            }
+
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        Ok(None)
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        let uri = item
+            .data
+            .as_ref()
+            .and_then(|x| x.get("uri"))
+            .and_then(|x| x.as_str())
+            .and_then(|x| Url::parse(x).ok());
+
+        let Some(uri) = uri else {
+            return Ok(item);
+        };
+
+        let document = self.documents.get(&uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(&uri, document.value()) else {
+                return Ok(item);
+            };
+
+            return Ok(completion::resolve_item(&ast, document.value(), item));
+        }
+
+        Ok(item)
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            return Ok(signature_help::signature_help_at(&ast, offset));
+        }
+
+        Ok(None)
     }
 
-    async fn completion(&self, _: CompletionParams) -> Result<Option<CompletionResponse>> {
-        // Return empty completion list for now
-        Ok(Some(CompletionResponse::Array(vec![])))
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let actions = code_actions::code_actions(
+                &ast,
+                document.value(),
+                self.encoding(),
+                uri,
+                &params.range,
+                &params.context.diagnostics,
+            );
+
+            return Ok(Some(actions));
+        }
+
+        Ok(None)
     }
 
     async fn semantic_tokens_full(
@@ -82,45 +359,86 @@ impl LanguageServer for Context {
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
         let uri = &params.text_document.uri;
-        let document = self.documents.get(uri);
-
-        if let Some(document) = document {
-            let text = document.value().to_string();
-            let rope = document.value();
+        let work_done = params.work_done_progress_params.work_done_token;
 
-            let ast = match tx3_lang::parsing::parse_string(text.as_str()) {
-                Ok(ast) => ast,
-                Err(_) => return Ok(None),
+        if let Some(token) = &work_done {
+            let begin = WorkDoneProgressBegin {
+                title: "Collecting semantic tokens".to_string(),
+                ..Default::default()
             };
 
-            let tokens = self.collect_semantic_tokens(&ast, rope);
+            self.report_work_done(token, WorkDoneProgress::Begin(begin))
+                .await;
+        }
+
+        let tokens = self.document_semantic_tokens(uri);
 
-            Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        let result = match (tokens, params.partial_result_params.partial_result_token) {
+            (None, _) => None,
+            (Some(data), None) => Some(SemanticTokensResult::Tokens(SemanticTokens {
                 result_id: None,
-                data: tokens,
-            })))
-        } else {
-            Ok(None)
+                data,
+            })),
+            (Some(data), Some(token)) => {
+                // deltas carry over between chunks, so the client just
+                // concatenates them
+                for chunk in data.chunks(SEMANTIC_TOKENS_CHUNK) {
+                    self.client
+                        .send_notification::<SemanticTokensProgress>(SemanticTokensProgressParams {
+                            token: token.clone(),
+                            value: SemanticTokensPartialResult {
+                                data: chunk.to_vec(),
+                            },
+                        })
+                        .await;
+                }
+
+                // every token went out as a partial result, so the response
+                // itself is left empty
+                Some(SemanticTokensResult::Partial(SemanticTokensPartialResult {
+                    data: vec![],
+                }))
+            }
+        };
+
+        if let Some(token) = &work_done {
+            let end = WorkDoneProgressEnd::default();
+            self.report_work_done(token, WorkDoneProgress::End(end))
+                .await;
         }
+
+        Ok(result)
     }
 
     async fn semantic_tokens_range(
         &self,
         params: SemanticTokensRangeParams,
     ) -> Result<Option<SemanticTokensRangeResult>> {
-        // TODO: optimize this for the specific range
-        let full_params = SemanticTokensParams {
-            text_document: params.text_document,
-            work_done_progress_params: params.work_done_progress_params,
-            partial_result_params: params.partial_result_params,
+        let uri = &params.text_document.uri;
+
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(None);
         };
 
-        self.semantic_tokens_full(full_params).await.map(|result| {
-            result.map(|tokens| match tokens {
-                SemanticTokensResult::Tokens(t) => SemanticTokensRangeResult::Tokens(t),
-                SemanticTokensResult::Partial(p) => SemanticTokensRangeResult::Partial(p),
-            })
-        })
+        let rope = document.value();
+
+        let Some(ast) = self.get_document_ast(uri, rope) else {
+            return Ok(None);
+        };
+
+        let range = (
+            position_to_offset(rope, self.encoding(), params.range.start),
+            position_to_offset(rope, self.encoding(), params.range.end),
+        );
+
+        // deltas are still relative to the previous token, the first one
+        // being relative to the start of the document as the spec requires
+        let tokens = self.collect_semantic_tokens(&ast, rope, Some(range));
+
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: tokens,
+        })))
     }
 
     async fn goto_definition(
@@ -130,16 +448,16 @@ impl LanguageServer for Context {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
+        // a name that isn't declared in this document may be in another one
+        let mut unresolved: Option<String> = None;
+
         let document = self.documents.get(uri);
         if let Some(document) = document {
-            let text = document.value().to_string();
-
-            let ast = match tx3_lang::parsing::parse_string(text.as_str()) {
-                Ok(ast) => ast,
-                Err(_) => return Ok(None),
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
             };
 
-            let offset = position_to_offset(&text, position);
+            let offset = position_to_offset(document.value(), self.encoding(), position);
 
             if let Some(symbol) = find_symbol_in_program(&ast, offset) {
                 let identifier = match symbol {
@@ -150,31 +468,53 @@ impl LanguageServer for Context {
                     },
                 };
 
-                for party in &ast.parties {
-                    if party.name.value == identifier.value {
-                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                            uri: uri.clone(),
-                            range: span_to_lsp_range(document.value(), &party.span),
-                        })));
+                // field names in a constructor point at the field in the type
+                for sc in collect_struct_constructors(&ast) {
+                    if !sc
+                        .case
+                        .fields
+                        .iter()
+                        // Span's equality holds whenever either side is a dummy
+                        .any(|x| {
+                            x.name.span.start == identifier.span.start
+                                && x.name.span.end == identifier.span.end
+                        })
+                    {
+                        continue;
                     }
-                }
 
-                for policy in &ast.policies {
-                    if policy.name.value == identifier.value {
-                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                            uri: uri.clone(),
-                            range: span_to_lsp_range(document.value(), &policy.span),
-                        })));
-                    }
+                    let Some((ty, case)) = references::resolve_constructor(&ast, sc) else {
+                        return Ok(None);
+                    };
+
+                    let span = case
+                        .and_then(|case| {
+                            case.fields
+                                .iter()
+                                .find(|x| x.name.value == identifier.value)
+                        })
+                        .map(|field| &field.span)
+                        .unwrap_or(&ty.span);
+
+                    return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                        uri: uri.clone(),
+                        range: span_to_lsp_range(document.value(), self.encoding(), span),
+                    })));
                 }
 
+                // names local to the enclosing tx (eg: an input used as the ref
+                // of a reference block) shadow the top-level ones
                 for tx in &ast.txs {
                     if span_contains(&tx.span, offset) {
                         for param in &tx.parameters.parameters {
                             if param.name.value == identifier.value {
                                 return Ok(Some(GotoDefinitionResponse::Scalar(Location {
                                     uri: uri.clone(),
-                                    range: span_to_lsp_range(document.value(), &tx.parameters.span),
+                                    range: span_to_lsp_range(
+                                        document.value(),
+                                        self.encoding(),
+                                        &tx.parameters.span,
+                                    ),
                                 })));
                             }
                         }
@@ -183,7 +523,11 @@ impl LanguageServer for Context {
                             if input.name == identifier.value {
                                 return Ok(Some(GotoDefinitionResponse::Scalar(Location {
                                     uri: uri.clone(),
-                                    range: span_to_lsp_range(document.value(), &input.span),
+                                    range: span_to_lsp_range(
+                                        document.value(),
+                                        self.encoding(),
+                                        &input.span,
+                                    ),
                                 })));
                             }
                         }
@@ -193,7 +537,11 @@ impl LanguageServer for Context {
                                 if output_name == identifier {
                                     return Ok(Some(GotoDefinitionResponse::Scalar(Location {
                                         uri: uri.clone(),
-                                        range: span_to_lsp_range(document.value(), &output.span),
+                                        range: span_to_lsp_range(
+                                            document.value(),
+                                            self.encoding(),
+                                            &output.span,
+                                        ),
                                     })));
                                 }
                             }
@@ -203,152 +551,807 @@ impl LanguageServer for Context {
                             if reference.name == identifier.value {
                                 return Ok(Some(GotoDefinitionResponse::Scalar(Location {
                                     uri: uri.clone(),
-                                    range: span_to_lsp_range(document.value(), &reference.span),
+                                    range: span_to_lsp_range(
+                                        document.value(),
+                                        self.encoding(),
+                                        &reference.span,
+                                    ),
                                 })));
                             }
                         }
                     }
                 }
+
+                for party in &ast.parties {
+                    if party.name.value == identifier.value {
+                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                            uri: uri.clone(),
+                            range: span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &party.span,
+                            ),
+                        })));
+                    }
+                }
+
+                for policy in &ast.policies {
+                    if policy.name.value == identifier.value {
+                        // a policy is defined by the value it's bound to, the
+                        // `policy` line itself is its declaration
+                        let span = match &policy.value {
+                            PolicyValue::Assign(x) => &x.span,
+                            PolicyValue::Constructor(x) => &x.span,
+                        };
+
+                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                            uri: uri.clone(),
+                            range: span_to_lsp_range(document.value(), self.encoding(), span),
+                        })));
+                    }
+                }
+
+                for type_def in &ast.types {
+                    if type_def.name.value == identifier.value {
+                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                            uri: uri.clone(),
+                            range: span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &type_def.span,
+                            ),
+                        })));
+                    }
+                }
+
+                for asset in &ast.assets {
+                    if asset.name.value == identifier.value {
+                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                            uri: uri.clone(),
+                            range: span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &asset.span,
+                            ),
+                        })));
+                    }
+                }
+
+                unresolved = Some(identifier.value.clone());
+            }
+        }
+
+        let location = unresolved.and_then(|name| self.find_in_other_documents(uri, &name));
+
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn goto_declaration(
+        &self,
+        params: GotoDeclarationParams,
+    ) -> Result<Option<GotoDeclarationResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            if let Some(span) = references::declaration_at(&ast, offset) {
+                return Ok(Some(GotoDeclarationResponse::Scalar(Location {
+                    uri: uri.clone(),
+                    range: span_to_lsp_range(document.value(), self.encoding(), span),
+                })));
             }
         }
 
         Ok(None)
     }
 
-    async fn references(&self, _: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        // Return empty references list for now
-        Ok(Some(vec![]))
+    async fn goto_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> Result<Option<GotoTypeDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            if let Some(ty) = references::type_definition_at(&ast, offset) {
+                return Ok(Some(GotoTypeDefinitionResponse::Scalar(Location {
+                    uri: uri.clone(),
+                    range: span_to_lsp_range(document.value(), self.encoding(), &ty.span),
+                })));
+            }
+        }
+
+        Ok(None)
     }
 
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+    async fn goto_implementation(
+        &self,
+        params: GotoImplementationParams,
+    ) -> Result<Option<GotoImplementationResponse>> {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
         let document = self.documents.get(uri);
         if let Some(document) = document {
-            let text = document.value().to_string();
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
 
-            let ast = match tx3_lang::parsing::parse_string(text.as_str()) {
-                Ok(ast) => ast,
-                Err(_) => return Ok(None),
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let Some(identifier) = references::symbol_at(&ast, offset) else {
+                return Ok(None);
             };
 
-            let offset = position_to_offset(&text, position);
+            // a script-backed party is implemented by the policy of the same name
+            let Some(policy) = ast
+                .policies
+                .iter()
+                .find(|x| x.name.value == identifier.value)
+            else {
+                return Ok(None);
+            };
 
-            for party in &ast.parties {
-                if span_contains(&party.span, offset) {
-                    return Ok(Some(Hover {
-                    contents: HoverContents::Markup(MarkupContent {
-                        kind: MarkupKind::Markdown,
-                        value: format!(
-                            "**Party**: `{}`\n\nA party in the transaction. It can be an address for a script or a wallet.",
-                            party.name.value
-                        ),
-                    }),
-                    range: Some(span_to_lsp_range(document.value(), &party.span)),
-                }));
-                }
-            }
+            let tx3_lang::ast::PolicyValue::Constructor(constr) = &policy.value else {
+                return Ok(None);
+            };
 
-            for policy in &ast.policies {
-                if span_contains(&policy.span, offset) {
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Markup(MarkupContent {
-                            kind: MarkupKind::Markdown,
-                            value: format!(
-                                "**Policy**: `{}`\n\nA policy definition.",
-                                policy.name.value
-                            ),
-                        }),
-                        range: Some(span_to_lsp_range(document.value(), &policy.span)),
-                    }));
-                }
-            }
+            let implementation = constr.fields.iter().find_map(|field| match field {
+                tx3_lang::ast::PolicyField::Script(expr)
+                | tx3_lang::ast::PolicyField::Ref(expr) => Some(expr),
+                tx3_lang::ast::PolicyField::Hash(_) => None,
+            });
 
-            for type_def in &ast.types {
-                if span_contains(&type_def.span, offset) {
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Markup(MarkupContent {
-                            kind: MarkupKind::Markdown,
-                            value: format!(
-                                "**Type**: `{}`\n\nA type definition.",
-                                type_def.name.value
-                            ),
-                        }),
-                        range: Some(span_to_lsp_range(document.value(), &type_def.span)),
-                    }));
+            let span = match implementation {
+                Some(tx3_lang::ast::DataExpr::Identifier(x)) => {
+                    references::global_declaration(&ast, &x.value).unwrap_or(&x.span)
                 }
-            }
-
-            for asset in &ast.assets {
-                if span_contains(&asset.span, offset) {
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Markup(MarkupContent {
-                            kind: MarkupKind::Markdown,
-                            value: format!(
-                                "**Asset**: `{}`\n\nAn asset definition.",
-                                asset.name.value
-                            ),
-                        }),
-                        range: Some(span_to_lsp_range(document.value(), &asset.span)),
-                    }));
+                Some(tx3_lang::ast::DataExpr::HexString(x)) => &x.span,
+                Some(_) => &policy.span,
+                None if ast.parties.iter().any(|x| x.name.value == identifier.value) => {
+                    &policy.span
                 }
-            }
+                None => return Ok(None),
+            };
 
-            for tx in &ast.txs {
-                for input in &tx.inputs {
-                    if span_contains(&input.span, offset) {
-                        return Ok(Some(Hover {
-                            contents: HoverContents::Markup(MarkupContent {
-                                kind: MarkupKind::Markdown,
-                                value: format!("**Input**: `{}`\n\nTransaction input.", input.name),
-                            }),
-                            range: Some(span_to_lsp_range(document.value(), &input.span)),
-                        }));
-                    }
-                }
+            return Ok(Some(GotoImplementationResponse::Scalar(Location {
+                uri: uri.clone(),
+                range: span_to_lsp_range(document.value(), self.encoding(), span),
+            })));
+        }
 
-                for (i, output) in tx.outputs.iter().enumerate() {
-                    if span_contains(&output.span, offset) {
-                        let default_output = Identifier::new(format!("output {}", i + 1));
-                        let name = output.name.as_ref().unwrap_or(&default_output);
-                        return Ok(Some(Hover {
-                            contents: HoverContents::Markup(MarkupContent {
+        Ok(None)
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let item =
+                call_hierarchy::prepare(&ast, document.value(), self.encoding(), uri, offset);
+            return Ok(item.map(|x| vec![x]));
+        }
+
+        Ok(None)
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let uri = &params.item.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let calls = call_hierarchy::incoming_calls(
+                &ast,
+                document.value(),
+                self.encoding(),
+                uri,
+                &params.item.name,
+            );
+            return Ok(Some(calls));
+        }
+
+        Ok(None)
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let Some((_, _, occurrences)) = references::resolve_at(&ast, offset) else {
+                return Ok(None);
+            };
+
+            let locations = occurrences
+                .into_iter()
+                .filter(|x| params.context.include_declaration || x.role != Role::Declaration)
+                .map(|x| Location {
+                    uri: uri.clone(),
+                    range: span_to_lsp_range(document.value(), self.encoding(), &x.identifier.span),
+                })
+                .collect();
+
+            return Ok(Some(locations));
+        }
+
+        Ok(None)
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let Some((_, _, occurrences)) = references::resolve_at(&ast, offset) else {
+                return Ok(None);
+            };
+
+            let highlights = occurrences
+                .into_iter()
+                .map(|x| DocumentHighlight {
+                    range: span_to_lsp_range(document.value(), self.encoding(), &x.identifier.span),
+                    kind: Some(match x.role {
+                        Role::Declaration => DocumentHighlightKind::WRITE,
+                        _ => DocumentHighlightKind::READ,
+                    }),
+                })
+                .collect();
+
+            return Ok(Some(highlights));
+        }
+
+        Ok(None)
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let uri = &params.text_document.uri;
+        let position = params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let Some(identifier) = references::symbol_at(&ast, offset) else {
+                return Ok(None);
+            };
+
+            // only symbols with a declaration in this document can be renamed
+            let declared = match references::resolve_scope(&ast, &identifier.value, offset) {
+                references::Scope::Tx(_) => true,
+                references::Scope::Global => {
+                    references::global_declaration(&ast, &identifier.value).is_some()
+                }
+            };
+
+            if !declared {
+                return Ok(None);
+            }
+
+            return Ok(Some(PrepareRenameResponse::Range(span_to_lsp_range(
+                document.value(),
+                self.encoding(),
+                &identifier.span,
+            ))));
+        }
+
+        Ok(None)
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            let Some((identifier, scope, occurrences)) = references::resolve_at(&ast, offset)
+            else {
+                return Ok(None);
+            };
+
+            if params.new_name != identifier.value
+                && references::is_rename_conflict(&ast, &identifier.value, &params.new_name, scope)
+            {
+                return Err(Error::NameCollision(params.new_name).into());
+            }
+
+            let edits = occurrences
+                .into_iter()
+                .map(|x| TextEdit {
+                    range: span_to_lsp_range(document.value(), self.encoding(), &x.identifier.span),
+                    new_text: params.new_name.clone(),
+                })
+                .collect();
+
+            return Ok(Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), edits)])),
+                ..Default::default()
+            }));
+        }
+
+        Ok(None)
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let offset = position_to_offset(document.value(), self.encoding(), position);
+
+            for party in &ast.parties {
+                if span_contains(&party.span, offset) {
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: with_doc_comment(
+                                format!(
+                                    "**Party**: `{}`\n\nA party in the transaction. It can be an address for a script or a wallet.",
+                                    party.name.value
+                                ),
+                                document.value(),
+                                &party.span,
+                            ),
+                        }),
+                        range: Some(span_to_lsp_range(document.value(), self.encoding(), &party.span)),
+                    }));
+                }
+            }
+
+            for policy in &ast.policies {
+                if span_contains(&policy.span, offset) {
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: with_doc_comment(
+                                format!(
+                                    "**Policy**: `{}`\n\n{}",
+                                    policy.name.value,
+                                    policy_value_to_text(policy)
+                                ),
+                                document.value(),
+                                &policy.span,
+                            ),
+                        }),
+                        range: Some(span_to_lsp_range(
+                            document.value(),
+                            self.encoding(),
+                            &policy.span,
+                        )),
+                    }));
+                }
+            }
+
+            for type_def in &ast.types {
+                for case in &type_def.cases {
+                    for field in &case.fields {
+                        if span_contains(&field.span, offset) {
+                            return Ok(Some(Hover {
+                                contents: HoverContents::Markup(MarkupContent {
+                                    kind: MarkupKind::Markdown,
+                                    value: format!(
+                                        "**Field**: `{}`\n\n**Type**: `{}`",
+                                        field.name.value,
+                                        type_to_text(&field.r#type)
+                                    ),
+                                }),
+                                range: Some(span_to_lsp_range(
+                                    document.value(),
+                                    self.encoding(),
+                                    &field.span,
+                                )),
+                            }));
+                        }
+                    }
+
+                    // records have a single implicit case, there's nothing specific to show
+                    let is_implicit = case.name.span.start == case.name.span.end;
+
+                    if !is_implicit && span_contains(&case.span, offset) {
+                        let mut hover_text = format!(
+                            "**Case**: `{}::{}`\n\n",
+                            type_def.name.value, case.name.value
+                        );
+
+                        for field in &case.fields {
+                            hover_text.push_str(&format!(
+                                "- `{}`: `{}`\n",
+                                field.name.value,
+                                type_to_text(&field.r#type)
+                            ));
+                        }
+
+                        return Ok(Some(Hover {
+                            contents: HoverContents::Markup(MarkupContent {
+                                kind: MarkupKind::Markdown,
+                                value: hover_text,
+                            }),
+                            range: Some(span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &case.span,
+                            )),
+                        }));
+                    }
+                }
+
+                if span_contains(&type_def.span, offset) {
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: with_doc_comment(
+                                format!(
+                                    "**Type**: `{}`\n\nA type definition.",
+                                    type_def.name.value
+                                ),
+                                document.value(),
+                                &type_def.span,
+                            ),
+                        }),
+                        range: Some(span_to_lsp_range(
+                            document.value(),
+                            self.encoding(),
+                            &type_def.span,
+                        )),
+                    }));
+                }
+            }
+
+            for asset in &ast.assets {
+                if span_contains(&asset.span, offset) {
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: with_doc_comment(
+                                format!(
+                                    "**Asset**: `{}`\n\nAn asset definition.",
+                                    asset.name.value
+                                ),
+                                document.value(),
+                                &asset.span,
+                            ),
+                        }),
+                        range: Some(span_to_lsp_range(
+                            document.value(),
+                            self.encoding(),
+                            &asset.span,
+                        )),
+                    }));
+                }
+            }
+
+            for tx in &ast.txs {
+                // amounts get a breakdown of the assets they add up to
+                let inputs = tx
+                    .inputs
+                    .iter()
+                    .flat_map(|x| &x.fields)
+                    .filter_map(|f| match f {
+                        InputBlockField::MinAmount(x) => Some(x),
+                        _ => None,
+                    });
+                let outputs = tx
+                    .outputs
+                    .iter()
+                    .flat_map(|x| &x.fields)
+                    .filter_map(|f| match f {
+                        OutputBlockField::Amount(x) => Some(x.as_ref()),
+                        _ => None,
+                    });
+                let supply = tx
+                    .mints
+                    .iter()
+                    .chain(&tx.burns)
+                    .flat_map(|x| &x.fields)
+                    .filter_map(|f| match f {
+                        MintBlockField::Amount(x) => Some(x.as_ref()),
+                        _ => None,
+                    });
+                let collateral =
+                    tx.collateral
+                        .iter()
+                        .flat_map(|x| &x.fields)
+                        .filter_map(|f| match f {
+                            CollateralBlockField::MinAmount(x) => Some(x),
+                            _ => None,
+                        });
+
+                for amount in inputs.chain(outputs).chain(supply).chain(collateral) {
+                    let Some(span) = data_expr_span(amount) else {
+                        continue;
+                    };
+
+                    if !span_contains(span, offset) {
+                        continue;
+                    }
+
+                    if let Some(text) = asset_expr_to_text(&ast, amount) {
+                        return Ok(Some(markdown_hover(
+                            format!("**Amount**\n\n{}", text),
+                            document.value(),
+                            self.encoding(),
+                            span,
+                        )));
+                    }
+                }
+
+                for input in &tx.inputs {
+                    if span_contains(&input.span, offset) {
+                        return Ok(Some(Hover {
+                            contents: HoverContents::Markup(MarkupContent {
+                                kind: MarkupKind::Markdown,
+                                value: format!("**Input**: `{}`\n\nTransaction input.", input.name),
+                            }),
+                            range: Some(span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &input.span,
+                            )),
+                        }));
+                    }
+                }
+
+                for (i, output) in tx.outputs.iter().enumerate() {
+                    if span_contains(&output.span, offset) {
+                        let default_output = Identifier::new(format!("output {}", i + 1));
+                        let name = output.name.as_ref().unwrap_or(&default_output);
+                        return Ok(Some(Hover {
+                            contents: HoverContents::Markup(MarkupContent {
                                 kind: MarkupKind::Markdown,
                                 value: format!(
                                     "**Output**: `{}`\n\nTransaction output.",
                                     name.value
                                 ),
                             }),
-                            range: Some(span_to_lsp_range(document.value(), &output.span)),
+                            range: Some(span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &output.span,
+                            )),
                         }));
                     }
                 }
 
                 if span_contains(&tx.parameters.span, offset) {
-                    for param in &tx.parameters.parameters {
+                    // the parameter under the cursor is the last one starting before it
+                    let param = tx
+                        .parameters
+                        .parameters
+                        .iter()
+                        .rev()
+                        .find(|x| x.name.span.start <= offset);
+
+                    if let Some(param) = param {
                         return Ok(Some(Hover {
                             contents: HoverContents::Markup(MarkupContent {
                                 kind: MarkupKind::Markdown,
                                 value: format!(
-                                    "**Parameter**: `{}`\n\n**Type**: `{:?}`",
-                                    param.name.value, param.r#type
+                                    "**Parameter**: `{}`\n\n**Type**: `{}`",
+                                    param.name.value,
+                                    type_to_text(&param.r#type)
                                 ),
                             }),
-                            range: Some(span_to_lsp_range(document.value(), &tx.parameters.span)),
+                            range: Some(span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &tx.parameters.span,
+                            )),
                         }));
                     }
                 }
 
+                let supply_changes = tx
+                    .mints
+                    .iter()
+                    .map(|x| ("Mint", x))
+                    .chain(tx.burns.iter().map(|x| ("Burn", x)));
+
+                for (kind, block) in supply_changes {
+                    if span_contains(&block.span, offset) {
+                        let mut hover_text = format!("**{}**\n\n", kind);
+
+                        for field in &block.fields {
+                            match field {
+                                MintBlockField::Amount(x) => hover_text
+                                    .push_str(&format!("- amount: `{}`\n", data_expr_to_text(x))),
+                                MintBlockField::Redeemer(x) => hover_text
+                                    .push_str(&format!("- redeemer: `{}`\n", data_expr_to_text(x))),
+                            }
+                        }
+
+                        return Ok(Some(markdown_hover(
+                            hover_text,
+                            document.value(),
+                            self.encoding(),
+                            &block.span,
+                        )));
+                    }
+                }
+
+                for reference in &tx.references {
+                    if span_contains(&reference.span, offset) {
+                        return Ok(Some(markdown_hover(
+                            format!(
+                                "**Reference**: `{}`\n\nUTxO read without being spent: `{}`",
+                                reference.name,
+                                data_expr_to_text(&reference.r#ref)
+                            ),
+                            document.value(),
+                            self.encoding(),
+                            &reference.span,
+                        )));
+                    }
+                }
+
+                for collateral in &tx.collateral {
+                    if span_contains(&collateral.span, offset) {
+                        let mut hover_text = "**Collateral**\n\n".to_string();
+
+                        for field in &collateral.fields {
+                            let (name, expr) = match field {
+                                CollateralBlockField::From(x) => ("from", x),
+                                CollateralBlockField::MinAmount(x) => ("min_amount", x),
+                                CollateralBlockField::Ref(x) => ("ref", x),
+                            };
+
+                            hover_text.push_str(&format!(
+                                "- {}: `{}`\n",
+                                name,
+                                data_expr_to_text(expr)
+                            ));
+                        }
+
+                        return Ok(Some(markdown_hover(
+                            hover_text,
+                            document.value(),
+                            self.encoding(),
+                            &collateral.span,
+                        )));
+                    }
+                }
+
+                if let Some(signers) = &tx.signers {
+                    if span_contains(&signers.span, offset) {
+                        let mut hover_text =
+                            "**Signers**\n\nParties required to sign the transaction:\n\n"
+                                .to_string();
+
+                        for signer in &signers.signers {
+                            hover_text.push_str(&format!("- `{}`\n", data_expr_to_text(signer)));
+                        }
+
+                        return Ok(Some(markdown_hover(
+                            hover_text,
+                            document.value(),
+                            self.encoding(),
+                            &signers.span,
+                        )));
+                    }
+                }
+
+                if let Some(validity) = &tx.validity {
+                    if span_contains(&validity.span, offset) {
+                        let mut hover_text =
+                            "**Validity**\n\nSlot range in which the transaction can be submitted:\n\n"
+                                .to_string();
+
+                        for field in &validity.fields {
+                            let (name, expr) = match field {
+                                ValidityBlockField::SinceSlot(x) => ("since slot", x),
+                                ValidityBlockField::UntilSlot(x) => ("until slot", x),
+                            };
+
+                            hover_text.push_str(&format!(
+                                "- {}: `{}`\n",
+                                name,
+                                data_expr_to_text(expr)
+                            ));
+                        }
+
+                        return Ok(Some(markdown_hover(
+                            hover_text,
+                            document.value(),
+                            self.encoding(),
+                            &validity.span,
+                        )));
+                    }
+                }
+
+                if let Some(metadata) = &tx.metadata {
+                    if span_contains(&metadata.span, offset) {
+                        return Ok(Some(markdown_hover(
+                            format!(
+                                "**Metadata**\n\n{} entries attached to the transaction.",
+                                metadata.fields.len()
+                            ),
+                            document.value(),
+                            self.encoding(),
+                            &metadata.span,
+                        )));
+                    }
+                }
+
+                // only reached when no inner element matched
                 if span_contains(&tx.span, offset) {
                     let mut hover_text = format!("**Transaction**: `{}`\n\n", tx.name.value);
 
+                    if let Some(doc) = doc_comment(document.value(), &tx.span) {
+                        hover_text.push_str(&format!("{}\n\n", doc));
+                    }
+
                     if !tx.parameters.parameters.is_empty() {
                         hover_text.push_str("**Parameters**:\n");
                         for param in &tx.parameters.parameters {
                             hover_text.push_str(&format!(
-                                "- `{}`: `{:?}`\n",
-                                param.name.value, param.r#type
+                                "- `{}`: `{}`\n",
+                                param.name.value,
+                                type_to_text(&param.r#type)
                             ));
                         }
                         hover_text.push_str("\n");
@@ -377,7 +1380,11 @@ impl LanguageServer for Context {
                             kind: MarkupKind::Markdown,
                             value: hover_text,
                         }),
-                        range: Some(span_to_lsp_range(document.value(), &tx.span)),
+                        range: Some(span_to_lsp_range(
+                            document.value(),
+                            self.encoding(),
+                            &tx.span,
+                        )),
                     }));
                 }
             }
@@ -415,48 +1422,112 @@ impl LanguageServer for Context {
         let uri = &params.text_document.uri;
         let document = self.documents.get(uri);
         if let Some(document) = document {
-            let text = document.value().to_string();
-            let ast = tx3_lang::parsing::parse_string(text.as_str());
-            if ast.is_ok() {
-                let ast = ast.unwrap();
-                for party in ast.parties {
+            if let Some(ast) = self.get_document_ast(uri, document.value()) {
+                for party in &ast.parties {
                     symbols.push(make_symbol(
                         party.name.value.clone(),
-                        "Party".to_string(),
+                        symbol_detail("Party", document.value(), &party.span),
                         SymbolKind::OBJECT,
-                        span_to_lsp_range(document.value(), &party.span),
+                        span_to_lsp_range(document.value(), self.encoding(), &party.span),
                         None,
                     ));
                 }
 
-                for policy in ast.policies {
+                for policy in &ast.policies {
                     symbols.push(make_symbol(
                         policy.name.value.clone(),
-                        "Policy".to_string(),
+                        symbol_detail("Policy", document.value(), &policy.span),
                         SymbolKind::KEY,
-                        span_to_lsp_range(document.value(), &policy.span),
+                        span_to_lsp_range(document.value(), self.encoding(), &policy.span),
+                        None,
+                    ));
+                }
+
+                for type_def in &ast.types {
+                    let field_symbols = |fields: &[tx3_lang::ast::RecordField]| {
+                        fields
+                            .iter()
+                            .map(|field| {
+                                make_symbol(
+                                    field.name.value.clone(),
+                                    type_to_text(&field.r#type),
+                                    SymbolKind::FIELD,
+                                    span_to_lsp_range(
+                                        document.value(),
+                                        self.encoding(),
+                                        &field.span,
+                                    ),
+                                    None,
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    };
+
+                    // a single case is a record, its fields belong to the type itself
+                    let (kind, children) = match type_def.cases.as_slice() {
+                        [case] => (SymbolKind::STRUCT, field_symbols(&case.fields)),
+                        cases => (
+                            SymbolKind::ENUM,
+                            cases
+                                .iter()
+                                .map(|case| {
+                                    make_symbol(
+                                        case.name.value.clone(),
+                                        "Case".to_string(),
+                                        SymbolKind::ENUM_MEMBER,
+                                        span_to_lsp_range(
+                                            document.value(),
+                                            self.encoding(),
+                                            &case.span,
+                                        ),
+                                        Some(field_symbols(&case.fields)),
+                                    )
+                                })
+                                .collect(),
+                        ),
+                    };
+
+                    symbols.push(make_symbol(
+                        type_def.name.value.clone(),
+                        symbol_detail("Type", document.value(), &type_def.span),
+                        kind,
+                        span_to_lsp_range(document.value(), self.encoding(), &type_def.span),
+                        Some(children),
+                    ));
+                }
+
+                for asset in &ast.assets {
+                    symbols.push(make_symbol(
+                        asset.name.value.clone(),
+                        symbol_detail("Asset", document.value(), &asset.span),
+                        SymbolKind::CONSTANT,
+                        span_to_lsp_range(document.value(), self.encoding(), &asset.span),
                         None,
                     ));
                 }
 
-                for tx in ast.txs {
+                for tx in &ast.txs {
                     let mut children: Vec<DocumentSymbol> = Vec::new();
-                    for parameter in tx.parameters.parameters {
+                    for parameter in &tx.parameters.parameters {
                         children.push(make_symbol(
                             parameter.name.value.clone(),
-                            format!("Parameter<{:?}>", parameter.r#type),
+                            format!("Parameter<{}>", type_to_text(&parameter.r#type)),
                             SymbolKind::FIELD,
-                            span_to_lsp_range(document.value(), &tx.parameters.span),
+                            span_to_lsp_range(
+                                document.value(),
+                                self.encoding(),
+                                &tx.parameters.span,
+                            ),
                             None,
                         ));
                     }
 
-                    for input in tx.inputs {
+                    for input in &tx.inputs {
                         children.push(make_symbol(
                             input.name.clone(),
                             "Input".to_string(),
                             SymbolKind::OBJECT,
-                            span_to_lsp_range(document.value(), &input.span),
+                            span_to_lsp_range(document.value(), self.encoding(), &input.span),
                             None,
                         ));
                     }
@@ -470,16 +1541,76 @@ impl LanguageServer for Context {
                             name.value.clone(),
                             "Output".to_string(),
                             SymbolKind::OBJECT,
-                            span_to_lsp_range(document.value(), &output.span),
+                            span_to_lsp_range(document.value(), self.encoding(), &output.span),
+                            None,
+                        ));
+                    }
+
+                    for reference in &tx.references {
+                        children.push(make_symbol(
+                            reference.name.clone(),
+                            "Reference".to_string(),
+                            SymbolKind::OBJECT,
+                            span_to_lsp_range(document.value(), self.encoding(), &reference.span),
+                            None,
+                        ));
+                    }
+
+                    for (i, mint) in tx.mints.iter().enumerate() {
+                        children.push(make_symbol(
+                            format!("mint {}", i + 1),
+                            "Mint".to_string(),
+                            SymbolKind::EVENT,
+                            span_to_lsp_range(document.value(), self.encoding(), &mint.span),
+                            None,
+                        ));
+                    }
+
+                    for (i, burn) in tx.burns.iter().enumerate() {
+                        children.push(make_symbol(
+                            format!("burn {}", i + 1),
+                            "Burn".to_string(),
+                            SymbolKind::EVENT,
+                            span_to_lsp_range(document.value(), self.encoding(), &burn.span),
+                            None,
+                        ));
+                    }
+
+                    for (i, collateral) in tx.collateral.iter().enumerate() {
+                        children.push(make_symbol(
+                            format!("collateral {}", i + 1),
+                            "Collateral".to_string(),
+                            SymbolKind::OBJECT,
+                            span_to_lsp_range(document.value(), self.encoding(), &collateral.span),
+                            None,
+                        ));
+                    }
+
+                    if let Some(signers) = &tx.signers {
+                        children.push(make_symbol(
+                            "signers".to_string(),
+                            format!("Signers ({})", signers.signers.len()),
+                            SymbolKind::ARRAY,
+                            span_to_lsp_range(document.value(), self.encoding(), &signers.span),
+                            None,
+                        ));
+                    }
+
+                    if let Some(validity) = &tx.validity {
+                        children.push(make_symbol(
+                            "validity".to_string(),
+                            "Validity".to_string(),
+                            SymbolKind::PROPERTY,
+                            span_to_lsp_range(document.value(), self.encoding(), &validity.span),
                             None,
                         ));
                     }
 
                     symbols.push(make_symbol(
                         tx.name.value.clone(),
-                        "Tx".to_string(),
+                        symbol_detail("Tx", document.value(), &tx.span),
                         SymbolKind::METHOD,
-                        span_to_lsp_range(document.value(), &tx.span),
+                        span_to_lsp_range(document.value(), self.encoding(), &tx.span),
                         Some(children),
                     ));
                 }
@@ -488,22 +1619,414 @@ impl LanguageServer for Context {
         Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
 
-    async fn symbol(&self, _: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
-        // Return empty workspace symbols list for now
-        Ok(Some(vec![]))
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            // a half-written document is left alone rather than mangled
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let text = document.value().to_string();
+
+            let Some(formatted) = formatting::format_document(&ast, &text) else {
+                return Ok(None);
+            };
+
+            if formatted == text {
+                return Ok(Some(vec![]));
+            }
+
+            let end = char_index_to_line_col(
+                document.value(),
+                self.encoding(),
+                document.value().len_chars(),
+            );
+
+            return Ok(Some(vec![TextEdit {
+                range: Range::new(
+                    Position::new(0, 0),
+                    Position::new(end.0 as u32, end.1 as u32),
+                ),
+                new_text: formatted,
+            }]));
+        }
+
+        Ok(None)
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let rope = document.value();
+
+            let Some(ast) = self.get_document_ast(uri, rope) else {
+                return Ok(None);
+            };
+
+            let start = position_to_offset(rope, self.encoding(), params.range.start);
+            let end = position_to_offset(rope, self.encoding(), params.range.end);
+
+            // the whole node under the selection is formatted, or just the
+            // selected lines when it spans several declarations
+            let (first, last) = match formatting::enclosing_node(&ast, start, end) {
+                Some(span) => (
+                    char_index_to_line_col(rope, self.encoding(), span.start).0,
+                    char_index_to_line_col(rope, self.encoding(), span.end).0,
+                ),
+                None => (
+                    params.range.start.line as usize,
+                    params.range.end.line as usize,
+                ),
+            };
+
+            let last = last.min(rope.len_lines() - 1);
+
+            let Some(formatted) =
+                formatting::format_line_range(&ast, &rope.to_string(), first, last)
+            else {
+                return Ok(None);
+            };
+
+            let last_len = line_end_col(rope, self.encoding(), last);
+
+            return Ok(Some(vec![TextEdit {
+                range: Range::new(
+                    Position::new(first as u32, 0),
+                    Position::new(last as u32, last_len as u32),
+                ),
+                new_text: formatted,
+            }]));
+        }
+
+        Ok(None)
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let line = params.text_document_position.position.line as usize;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let rope = document.value();
+
+            // without a sound structure the nesting can't be trusted
+            if self.get_document_ast(uri, rope).is_none() || line >= rope.len_lines() {
+                return Ok(None);
+            }
+
+            // the indentation only depends on what comes before the line
+            let end = rope.line_to_char(line + 1);
+            let text = rope.slice(..end).to_string();
+
+            let Some(indent) = formatting::line_indent(&text, line) else {
+                return Ok(None);
+            };
+
+            let current = rope
+                .line(line)
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect::<String>();
+
+            if current == indent {
+                return Ok(Some(vec![]));
+            }
+
+            return Ok(Some(vec![TextEdit {
+                range: Range::new(
+                    Position::new(line as u32, 0),
+                    Position::new(line as u32, current.chars().count() as u32),
+                ),
+                new_text: indent,
+            }]));
+        }
+
+        Ok(None)
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let mut lenses = Vec::new();
+
+            for tx in &ast.txs {
+                let (line, _) =
+                    char_index_to_line_col(document.value(), self.encoding(), tx.span.start);
+                let position = Position::new(line as u32, 0);
+                let range = Range::new(position, position);
+
+                let arguments = vec![json!(uri.to_string()), json!(tx.name.value)];
+
+                let commands = [
+                    ("Show Diagram", cmds::GENERATE_DIAGRAM),
+                    ("Generate TIR", cmds::GENERATE_TIR),
+                ];
+
+                for (title, command) in commands {
+                    lenses.push(CodeLens {
+                        range,
+                        command: Some(Command {
+                            title: title.to_string(),
+                            command: command.to_string(),
+                            arguments: Some(arguments.clone()),
+                        }),
+                        data: None,
+                    });
+                }
+            }
+
+            return Ok(Some(lenses));
+        }
+
+        Ok(None)
+    }
+
+    async fn document_color(&self, params: DocumentColorParams) -> Result<Vec<ColorInformation>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(vec![]);
+            };
+
+            return Ok(document_colors::document_colors(
+                &ast,
+                document.value(),
+                self.encoding(),
+            ));
+        }
+
+        Ok(vec![])
+    }
+
+    async fn color_presentation(
+        &self,
+        _: ColorPresentationParams,
+    ) -> Result<Vec<ColorPresentation>> {
+        // the colors are derived from the literals, there's nothing to pick
+        Ok(vec![])
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let config = self.config();
+
+            let links = document_links::document_links(
+                &ast,
+                document.value(),
+                self.encoding(),
+                config.address_explorer_url.as_deref(),
+                config.policy_explorer_url.as_deref(),
+            );
+
+            return Ok(Some(links));
+        }
+
+        Ok(None)
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let ranges = params
+                .positions
+                .into_iter()
+                .map(|position| {
+                    let offset = position_to_offset(document.value(), self.encoding(), position);
+                    selection_range::selection_range_at(
+                        &ast,
+                        document.value(),
+                        self.encoding(),
+                        position,
+                        offset,
+                    )
+                })
+                .collect();
+
+            return Ok(Some(ranges));
+        }
+
+        Ok(None)
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let mut spans: Vec<&Span> = Vec::new();
+
+            spans.extend(ast.types.iter().map(|x| &x.span));
+
+            for tx in &ast.txs {
+                spans.push(&tx.span);
+                spans.extend(tx.inputs.iter().map(|x| &x.span));
+                spans.extend(tx.outputs.iter().map(|x| &x.span));
+                spans.extend(tx.mints.iter().map(|x| &x.span));
+                spans.extend(tx.burns.iter().map(|x| &x.span));
+                spans.extend(tx.collateral.iter().map(|x| &x.span));
+            }
+
+            let ranges = spans
+                .into_iter()
+                .filter_map(|span| {
+                    let (start_line, _) =
+                        char_index_to_line_col(document.value(), self.encoding(), span.start);
+                    let (end_line, _) =
+                        char_index_to_line_col(document.value(), self.encoding(), span.end);
+
+                    // nothing to fold on a single line
+                    (end_line > start_line).then(|| FoldingRange {
+                        start_line: start_line as u32,
+                        end_line: end_line as u32,
+                        kind: Some(FoldingRangeKind::Region),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+
+            return Ok(Some(ranges));
+        }
+
+        Ok(None)
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = &params.text_document.uri;
+
+        let document = self.documents.get(uri);
+        if let Some(document) = document {
+            let Some(ast) = self.get_document_ast(uri, document.value()) else {
+                return Ok(None);
+            };
+
+            let hints =
+                inlay_hints::inlay_hints(&ast, document.value(), self.encoding(), &params.range);
+
+            return Ok(Some(hints));
+        }
+
+        Ok(None)
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let query = params.query.to_lowercase();
+        let mut symbols = Vec::new();
+
+        for (uri, file) in self.workspace_files() {
+            let ast = &file.ast;
+
+            let parties = ast
+                .parties
+                .iter()
+                .map(|x| (&x.name, SymbolKind::OBJECT, &x.span));
+            let policies = ast
+                .policies
+                .iter()
+                .map(|x| (&x.name, SymbolKind::KEY, &x.span));
+            let types = ast
+                .types
+                .iter()
+                .map(|x| (&x.name, SymbolKind::STRUCT, &x.span));
+            let assets = ast
+                .assets
+                .iter()
+                .map(|x| (&x.name, SymbolKind::CONSTANT, &x.span));
+            let txs = ast
+                .txs
+                .iter()
+                .map(|x| (&x.name, SymbolKind::METHOD, &x.span));
+
+            let declarations = parties
+                .chain(policies)
+                .chain(types)
+                .chain(assets)
+                .chain(txs);
+
+            for (name, kind, span) in declarations {
+                if !name.value.to_lowercase().contains(&query) {
+                    continue;
+                }
+
+                #[allow(deprecated)]
+                symbols.push(SymbolInformation {
+                    name: name.value.clone(),
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: span_to_lsp_range(&file.rope, self.encoding(), span),
+                    },
+                    container_name: None,
+                });
+            }
+        }
+
+        Ok(Some(symbols))
     }
 
     async fn symbol_resolve(&self, params: WorkspaceSymbol) -> Result<WorkspaceSymbol> {
-        dbg!(&params);
         Ok(params)
     }
 
     // TODO: not sure if using execute_command is a good idea, but it's the simplest way to return a value to the client without going outside of the lsp protocol
     async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
-        match cmds::handle_command(self, params).await {
+        let command = params.command.clone();
+
+        // a bug in a single command shouldn't take the whole server down with it
+        let result = AssertUnwindSafe(cmds::handle_command(self, params))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|panic| Err(Error::CommandPanicked(panic_message(panic.as_ref()))));
+
+        match result {
             Ok(x) => Ok(x),
             Err(e) => {
-                dbg!(&e);
+                self.client
+                    .log_message(MessageType::ERROR, format!("{}: {}", command, e))
+                    .await;
                 Err(e.into())
             }
         }
@@ -518,6 +2041,7 @@ impl LanguageServer for Context {
         let version = params.text_document.version;
         let text = params.text_document.text.as_str();
 
+        self.cancel_analysis(&uri);
         let diagnostics = self.process_document(uri.clone(), text).await;
 
         self.client
@@ -528,20 +2052,584 @@ impl LanguageServer for Context {
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.clone();
         let version = params.text_document.version;
-        let text = params
-            .content_changes
-            .first()
-            .map(|x| x.text.as_str())
-            .unwrap_or("");
+        let current = self.documents.get(&uri).map(|x| x.value().clone());
 
-        let diagnostics = self.process_document(uri.clone(), text).await;
+        // some clients skip `didOpen` when reconnecting, in which case the
+        // edits are applied on top of the file as it is on disk
+        let opened = current.is_none();
 
-        self.client
-            .publish_diagnostics(uri, diagnostics, Some(version))
-            .await;
+        if opened {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("received a change for {} before it was opened", uri),
+                )
+                .await;
+        }
+
+        let mut rope = current
+            .or_else(|| self.workspace.get(&uri).map(|x| x.rope.clone()))
+            .unwrap_or_default();
+
+        // clients may batch several edits; each one is relative to the text
+        // left by the previous, so they must be applied in order
+        for change in &params.content_changes {
+            apply_content_change(&mut rope, self.encoding(), change);
+        }
+
+        if opened {
+            // handled like `didOpen`, so the document is known right away
+            self.cancel_analysis(&uri);
+            let diagnostics = self.process_document(uri.clone(), &rope.to_string()).await;
+
+            self.client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+
+            return;
+        }
+
+        self.schedule_analysis(uri, rope, version);
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            if !is_tx3_file(&change.uri) {
+                continue;
+            }
+
+            match change.typ {
+                FileChangeType::DELETED => self.unindex_file(&change.uri),
+                _ => self.index_file(&change.uri).await,
+            }
+        }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.cancel_analysis(&params.text_document.uri);
         self.documents.remove(&params.text_document.uri);
+        self.asts.remove(&params.text_document.uri);
+        self.analyses.remove(&params.text_document.uri);
+    }
+}
+
+impl Context {
+    /// Collects the semantic tokens of an open document, as long as it parses.
+    fn document_semantic_tokens(&self, uri: &Url) -> Option<Vec<SemanticToken>> {
+        let document = self.documents.get(uri)?;
+        let rope = document.value();
+
+        let ast = self.get_document_ast(uri, rope)?;
+
+        Some(self.collect_semantic_tokens(&ast, rope, None))
+    }
+
+    async fn report_work_done(&self, token: &ProgressToken, value: WorkDoneProgress) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
+
+    /// Handles `$/tx3/commands`, listing the commands available through
+    /// `workspace/executeCommand`.
+    pub async fn commands(&self) -> Result<Value> {
+        let commands: Vec<Value> = cmds::COMMANDS
+            .iter()
+            .map(|cmd| {
+                json!({
+                    "name": cmd.name,
+                    "description": cmd.description,
+                })
+            })
+            .collect();
+
+        Ok(Value::Array(commands))
+    }
+
+    /// Handles `$/tx3/resolveType`, expanding a type into its nested field
+    /// structure.
+    pub async fn resolve_type(&self, params: Value) -> Result<Value> {
+        let arg = |name: &str| {
+            params
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+                .ok_or(Error::InvalidCommandArgs(name.to_string()))
+        };
+
+        let document_url = arg("document_url")?;
+        let type_name = arg("type_name")?;
+
+        let program = self.get_document_program(&document_url)?;
+
+        let schema =
+            schema::resolve_type(&program, &type_name).ok_or(Error::TypeNotFound(type_name))?;
+
+        Ok(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tower::{Service, ServiceExt};
+    use tower_lsp::{jsonrpc::Request, LspService};
+
+    use super::*;
+
+    /// Opens `text` on a server that was never initialized, which keeps it
+    /// from sending anything to the client.
+    async fn open(text: &str) -> (LspService<Context>, Url) {
+        let (service, _) = LspService::new(Context::new_for_client);
+        let uri = Url::parse("file:///test.tx3").unwrap();
+
+        service
+            .inner()
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "tx3".into(), 1, text.into()),
+            })
+            .await;
+
+        (service, uri)
+    }
+
+    /// The start of the `nth` occurrence of `needle` in `text`.
+    fn position_of(text: &str, needle: &str, nth: usize) -> Position {
+        let (offset, _) = text.match_indices(needle).nth(nth).unwrap();
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+        Position::new(
+            before.matches('\n').count() as u32,
+            (offset - line_start) as u32,
+        )
+    }
+
+    fn at(uri: &Url, position: Position) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri.clone()), position)
+    }
+
+    async fn rename(text: &str, needle: &str, new_name: &str) -> Result<Option<WorkspaceEdit>> {
+        let (service, uri) = open(text).await;
+
+        service
+            .inner()
+            .rename(RenameParams {
+                text_document_position: at(&uri, position_of(text, needle, 0)),
+                new_name: new_name.to_string(),
+                work_done_progress_params: Default::default(),
+            })
+            .await
+    }
+
+    const TRANSFER: &str = r#"party Sender;
+party Receiver;
+
+tx transfer(quantity: Int, fee: Int) {
+    input source {
+        from: Sender,
+        min_amount: Ada(quantity) + Ada(fee),
+    }
+    output {
+        to: Receiver,
+        amount: Ada(quantity),
+    }
+}
+"#;
+
+    #[tokio::test]
+    async fn renaming_into_a_taken_name_is_rejected() {
+        for (needle, new_name) in [("Sender", "Receiver"), ("quantity", "fee")] {
+            let err = rename(TRANSFER, needle, new_name).await.unwrap_err();
+
+            assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+            assert_eq!(err.message, format!("Name already in use: {}", new_name));
+        }
+    }
+
+    #[tokio::test]
+    async fn renaming_into_a_free_name_edits_every_use() {
+        let edit = rename(TRANSFER, "Sender", "Buyer").await.unwrap().unwrap();
+        let edits = &edit.changes.unwrap()[&Url::parse("file:///test.tx3").unwrap()];
+
+        let mut ranges: Vec<_> = edits.iter().map(|x| x.range.start).collect();
+        ranges.sort();
+
+        assert_eq!(
+            ranges,
+            vec![
+                position_of(TRANSFER, "Sender", 0),
+                position_of(TRANSFER, "Sender", 1),
+            ]
+        );
+        assert!(edits.iter().all(|x| x.new_text == "Buyer"));
+    }
+
+    #[tokio::test]
+    async fn blank_documents_have_nothing_to_report() {
+        for text in ["", "  \n\t\n"] {
+            let (service, uri) = open(text).await;
+            let context = service.inner();
+
+            let diagnostics = context.process_document(uri.clone(), text).await;
+            assert!(diagnostics.is_empty());
+
+            for position in [Position::new(0, 0), Position::new(1, 1)] {
+                let hover = context
+                    .hover(HoverParams {
+                        text_document_position_params: at(&uri, position),
+                        work_done_progress_params: Default::default(),
+                    })
+                    .await;
+                assert_eq!(hover, Ok(None));
+
+                let definition = context
+                    .goto_definition(GotoDefinitionParams {
+                        text_document_position_params: at(&uri, position),
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .await;
+                assert_eq!(definition, Ok(None));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn listed_commands_match_the_advertised_ones() {
+        let (service, _) = LspService::new(Context::new_for_client);
+        let context = service.inner();
+
+        let result = context.initialize(Default::default()).await.unwrap();
+        let advertised = result
+            .capabilities
+            .execute_command_provider
+            .unwrap()
+            .commands;
+
+        let listed = context.commands().await.unwrap();
+        let listed: Vec<_> = listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["name"].as_str().unwrap().to_string())
+            .collect();
+
+        assert_eq!(listed, advertised);
+        assert!(listed.iter().any(|x| x == cmds::GENERATE_DIAGRAM));
+    }
+
+    const POLICIES: &str = r#"policy Minter {
+    hash: 0x0011,
+    script: 0xcafe,
+}
+
+policy Locker {
+    hash: 0x0022,
+    ref: 0xbeef,
+}
+
+policy Plain = 0x0033;
+
+tx spend() {
+    input a {
+        from: Minter,
+        min_amount: Ada(1),
+    }
+    input b {
+        from: Locker,
+        min_amount: Ada(1),
+    }
+    input c {
+        from: Plain,
+        min_amount: Ada(1),
+    }
+}
+"#;
+
+    async fn implementation_line(needle: &str) -> Option<u32> {
+        let (service, uri) = open(POLICIES).await;
+
+        let response = service
+            .inner()
+            .goto_implementation(GotoImplementationParams {
+                text_document_position_params: at(&uri, position_of(POLICIES, needle, 1)),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()?;
+
+        let GotoImplementationResponse::Scalar(location) = response else {
+            panic!("expected a single location");
+        };
+
+        assert_eq!(location.range.start.line, location.range.end.line);
+        Some(location.range.start.line)
+    }
+
+    #[tokio::test]
+    async fn policies_are_implemented_by_their_script_or_ref() {
+        let script = position_of(POLICIES, "0xcafe", 0).line;
+        assert_eq!(implementation_line("Minter").await, Some(script));
+
+        let reference = position_of(POLICIES, "0xbeef", 0).line;
+        assert_eq!(implementation_line("Locker").await, Some(reference));
+
+        assert_eq!(implementation_line("Plain").await, None);
+    }
+
+    #[tokio::test]
+    async fn hover_shows_the_comment_above_a_declaration() {
+        let text = "party Sender;\n\n// The one paying.\nparty Receiver;\n";
+        let (service, uri) = open(text).await;
+
+        let hover = service
+            .inner()
+            .hover(HoverParams {
+                text_document_position_params: at(&uri, position_of(text, "Receiver", 0)),
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let HoverContents::Markup(content) = hover.contents else {
+            panic!("expected markdown");
+        };
+
+        assert!(content.value.starts_with("**Party**: `Receiver`"));
+        assert!(content.value.ends_with("\n\nThe one paying."));
+    }
+
+    async fn definition(text: &str, position: Position) -> Option<Range> {
+        let (service, uri) = open(text).await;
+
+        let response = service
+            .inner()
+            .goto_definition(GotoDefinitionParams {
+                text_document_position_params: at(&uri, position),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()?;
+
+        match response {
+            GotoDefinitionResponse::Scalar(location) => Some(location.range),
+            other => panic!("expected a single location, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn definitions_are_found_past_multibyte_chars() {
+        let text = r#"// señal 😀
+party Sender;
+
+tx pay() {
+    input source {
+        /* ñ😀 */ from: Sender,
+        min_amount: Ada(1),
+    }
+}
+"#;
+
+        // the emoji takes two UTF-16 units, so `Sender` starts at char 23
+        let range = definition(text, Position::new(5, 24)).await.unwrap();
+
+        assert_eq!(range.start, Position::new(1, 0));
+    }
+
+    const ORDER: &str = r#"party Sender;
+
+type Order {
+    amount: Int,
+}
+
+asset Token = 0x0011.TOKEN;
+
+tx pay(order: Order) {
+    input source {
+        from: Sender,
+        min_amount: Token(1),
+    }
+    output {
+        to: Sender,
+        amount: Token(1),
+        datum: Order { amount: 1 },
+    }
+}
+"#;
+
+    #[tokio::test]
+    async fn types_and_assets_resolve_to_their_declaration() {
+        let order = position_of(ORDER, "type Order", 0);
+        let token = position_of(ORDER, "asset Token", 0);
+
+        // the parameter type, then the datum constructor
+        for nth in [1, 2] {
+            let range = definition(ORDER, position_of(ORDER, "Order", nth)).await;
+            assert_eq!(range.map(|x| x.start), Some(order));
+        }
+
+        for nth in [1, 2] {
+            let range = definition(ORDER, position_of(ORDER, "Token", nth)).await;
+            assert_eq!(range.map(|x| x.start), Some(token));
+        }
+    }
+
+    #[tokio::test]
+    async fn names_in_reference_and_collateral_blocks_resolve() {
+        let text = r#"party Sender;
+
+tx lock() {
+    input source {
+        from: Sender,
+        min_amount: Ada(1),
+    }
+    reference script {
+        ref: source,
+    }
+    collateral {
+        from: Sender,
+        min_amount: Ada(5),
+    }
+}
+"#;
+
+        let range = definition(text, position_of(text, "Sender", 2)).await;
+        assert_eq!(range.map(|x| x.start), Some(position_of(text, "party", 0)));
+
+        let range = definition(text, position_of(text, "source", 1)).await;
+        assert_eq!(range.map(|x| x.start), Some(position_of(text, "input", 0)));
+    }
+
+    #[tokio::test]
+    async fn a_change_before_open_opens_the_document() {
+        let (service, _) = LspService::new(Context::new_for_client);
+        let context = service.inner();
+        let uri = Url::parse("file:///test.tx3").unwrap();
+        let text = "party Sender;\n";
+
+        context
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: text.to_string(),
+                }],
+            })
+            .await;
+
+        let document = context.documents.get(&uri).map(|x| x.value().to_string());
+        assert_eq!(document.as_deref(), Some(text));
+
+        // analyzed right away rather than debounced, like on open
+        assert!(context.asts.contains_key(&uri));
+        assert!(!context.pending.contains_key(&uri));
+    }
+
+    fn semantic_tokens_params(
+        uri: &Url,
+        partial_result_token: Option<&str>,
+    ) -> SemanticTokensParams {
+        SemanticTokensParams {
+            work_done_progress_params: Default::default(),
+            partial_result_params: PartialResultParams {
+                partial_result_token: partial_result_token
+                    .map(|x| ProgressToken::String(x.to_string())),
+            },
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn semantic_tokens_come_whole_without_a_partial_result_token() {
+        let (service, uri) = open(TRANSFER).await;
+
+        let result = service
+            .inner()
+            .semantic_tokens_full(semantic_tokens_params(&uri, None))
+            .await
+            .unwrap();
+
+        let Some(SemanticTokensResult::Tokens(tokens)) = result else {
+            panic!("expected every token in the response");
+        };
+        assert!(!tokens.data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn semantic_tokens_stream_as_partial_results_when_asked() {
+        let (mut service, socket) = LspService::new(Context::new_for_client);
+
+        // notifications only reach the client once it's initialized
+        let initialize = Request::build("initialize")
+            .params(json!({ "capabilities": {} }))
+            .id(1)
+            .finish();
+        service
+            .ready()
+            .await
+            .unwrap()
+            .call(initialize)
+            .await
+            .unwrap();
+
+        // keeps the partial results, draining anything else sent meanwhile
+        let partials = tokio::spawn(
+            socket
+                .filter(|x| std::future::ready(x.method() == "$/progress"))
+                .map(|x| {
+                    serde_json::from_value::<SemanticTokensProgressParams>(
+                        x.params().unwrap().clone(),
+                    )
+                    .unwrap()
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let uri = Url::parse("file:///test.tx3").unwrap();
+        let context = service.inner();
+
+        context
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "tx3".into(), 1, TRANSFER.into()),
+            })
+            .await;
+
+        let whole = context
+            .semantic_tokens_full(semantic_tokens_params(&uri, None))
+            .await
+            .unwrap();
+        let streamed = context
+            .semantic_tokens_full(semantic_tokens_params(&uri, Some("tokens")))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            streamed,
+            Some(SemanticTokensResult::Partial(SemanticTokensPartialResult {
+                data: vec![]
+            }))
+        );
+
+        // the client side closes along with the server
+        drop(service);
+        let partials = partials.await.unwrap();
+
+        let token = ProgressToken::String("tokens".to_string());
+        assert!(!partials.is_empty());
+        assert!(partials.iter().all(|x| x.token == token));
+
+        let Some(SemanticTokensResult::Tokens(whole)) = whole else {
+            panic!("expected every token in the response");
+        };
+        let data: Vec<_> = partials.into_iter().flat_map(|x| x.value.data).collect();
+        assert_eq!(data, whole.data);
     }
 }
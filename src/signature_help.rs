@@ -0,0 +1,71 @@
+use tower_lsp::lsp_types::{
+    ParameterInformation, ParameterLabel, SignatureHelp, SignatureInformation,
+};
+use tx3_lang::ast::Program;
+
+use crate::ast_to_text::type_to_text;
+use crate::references;
+use crate::visitor::collect_struct_constructors;
+
+/// Shows the fields of the struct constructor surrounding `offset`, with the
+/// field under the cursor as the active parameter.
+pub fn signature_help_at(ast: &Program, offset: usize) -> Option<SignatureHelp> {
+    let sc = collect_struct_constructors(ast)
+        .into_iter()
+        .filter(|sc| sc.span.start <= offset && offset <= sc.span.end)
+        .min_by_key(|sc| sc.span.end - sc.span.start)?;
+
+    let (ty, case) = references::resolve_constructor(ast, sc)?;
+    let case = case?;
+
+    let mut label = match ty.cases.len() {
+        1 => format!("{} {{ ", ty.name.value),
+        _ => format!("{}::{} {{ ", ty.name.value, case.name.value),
+    };
+
+    let mut parameters = Vec::new();
+
+    for (i, field) in case.fields.iter().enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+
+        let start = label.chars().count() as u32;
+        label.push_str(&format!(
+            "{}: {}",
+            field.name.value,
+            type_to_text(&field.r#type)
+        ));
+        let end = label.chars().count() as u32;
+
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+    }
+
+    label.push_str(" }");
+
+    let active_parameter = sc
+        .case
+        .fields
+        .iter()
+        .find(|x| x.span.start <= offset && offset <= x.span.end)
+        .and_then(|x| {
+            case.fields
+                .iter()
+                .position(|field| field.name.value == x.name.value)
+        })
+        .map(|x| x as u32);
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter,
+        }],
+        active_signature: Some(0),
+        active_parameter,
+    })
+}
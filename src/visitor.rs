@@ -194,6 +194,23 @@ fn visit_data_expr<'a>(
             }
             None
         }
+        tx3_lang::ast::DataExpr::FnCall(x) => {
+            if let Some(sym) = visit_identifier(&x.callee, offset) {
+                return Some(sym);
+            }
+            x.args.iter().find_map(|arg| visit_data_expr(arg, offset))
+        }
+        tx3_lang::ast::DataExpr::AnyAssetConstructor(x) => visit_data_expr(&x.policy, offset)
+            .or_else(|| visit_data_expr(&x.asset_name, offset))
+            .or_else(|| visit_data_expr(&x.amount, offset)),
+        tx3_lang::ast::DataExpr::AddOp(x) => {
+            visit_data_expr(&x.lhs, offset).or_else(|| visit_data_expr(&x.rhs, offset))
+        }
+        tx3_lang::ast::DataExpr::SubOp(x) => {
+            visit_data_expr(&x.lhs, offset).or_else(|| visit_data_expr(&x.rhs, offset))
+        }
+        tx3_lang::ast::DataExpr::NegateOp(x) => visit_data_expr(&x.operand, offset),
+        tx3_lang::ast::DataExpr::PropertyOp(x) => visit_data_expr(&x.operand, offset),
         _ => None,
     }
 }
@@ -442,3 +459,495 @@ fn visit_address_expr<'a>(
 fn in_span(span: &tx3_lang::ast::Span, offset: usize) -> bool {
     span.start <= offset && offset < span.end
 }
+
+/// An identifier reachable through `find_symbol_in_program`, along with the
+/// declarations that enclose it.
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolContext<'a> {
+    pub identifier: &'a tx3_lang::ast::Identifier,
+    /// The enclosing tx and its position within `program.txs`.
+    pub tx: Option<(usize, &'a tx3_lang::ast::TxDef)>,
+    pub type_def: Option<&'a tx3_lang::ast::TypeDef>,
+}
+
+/// The role an identifier plays at a given location in the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Introduces a party, policy, type, asset, tx, parameter or named output.
+    Declaration,
+    /// Uses a symbol declared elsewhere.
+    Reference,
+    /// Names a record field or a variant case, which live in their type's namespace.
+    Member,
+}
+
+#[derive(Debug, Clone)]
+pub struct Occurrence<'a> {
+    pub identifier: &'a tx3_lang::ast::Identifier,
+    pub role: Role,
+    pub tx: Option<&'a tx3_lang::ast::TxDef>,
+}
+
+/// Collects every identifier in the program, in traversal order.
+pub fn collect_occurrences(program: &tx3_lang::ast::Program) -> Vec<Occurrence<'_>> {
+    ProgramIndex::new(program).occurrences
+}
+
+/// Collects every property access (eg: `source.amount`) within a tx body,
+/// along with the tx that contains it.
+pub fn collect_property_ops(
+    program: &tx3_lang::ast::Program,
+) -> Vec<(&tx3_lang::ast::PropertyOp, &tx3_lang::ast::TxDef)> {
+    ProgramIndex::new(program).property_ops
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralKind {
+    String,
+    /// Hex strings and utxo refs (eg: `0xabcd#0`).
+    Bytes,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Literal<'a> {
+    pub span: &'a tx3_lang::ast::Span,
+    pub kind: LiteralKind,
+}
+
+/// Collects the string and bytes literals in the program. Numbers don't keep
+/// a span in the AST, so they're left to the lexer.
+pub fn collect_literals(program: &tx3_lang::ast::Program) -> Vec<Literal<'_>> {
+    ProgramIndex::new(program).literals
+}
+
+/// Collects every struct constructor (eg: `MyType { ... }`) in the program.
+pub fn collect_struct_constructors(
+    program: &tx3_lang::ast::Program,
+) -> Vec<&tx3_lang::ast::StructConstructor> {
+    ProgramIndex::new(program).struct_constructors
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AssetConstructor<'a> {
+    /// A declared asset applied to an amount, eg: `MyToken(100)`, which the
+    /// parser reads as a function call.
+    Call(&'a tx3_lang::ast::FnCall),
+    /// eg: `AnyAsset(policy, asset_name, amount)`.
+    Any(&'a tx3_lang::ast::AnyAssetConstructor),
+}
+
+/// Collects every asset constructor in the program, named or `AnyAsset`.
+pub fn collect_asset_constructors(program: &tx3_lang::ast::Program) -> Vec<AssetConstructor<'_>> {
+    ProgramIndex::new(program).asset_constructors
+}
+
+/// Everything the collectors above gather, filled by a single walk over the
+/// program. Build it once when a request needs more than one of them.
+pub struct ProgramIndex<'a> {
+    pub symbols: Vec<SymbolContext<'a>>,
+    pub occurrences: Vec<Occurrence<'a>>,
+    pub property_ops: Vec<(&'a tx3_lang::ast::PropertyOp, &'a tx3_lang::ast::TxDef)>,
+    pub struct_constructors: Vec<&'a tx3_lang::ast::StructConstructor>,
+    pub asset_constructors: Vec<AssetConstructor<'a>>,
+    pub literals: Vec<Literal<'a>>,
+    tx: Option<(usize, &'a tx3_lang::ast::TxDef)>,
+    type_def: Option<&'a tx3_lang::ast::TypeDef>,
+    /// Whether the identifiers being walked are reachable through
+    /// `find_symbol_in_program`, which skips some subtrees.
+    in_symbols: bool,
+}
+
+impl<'a> ProgramIndex<'a> {
+    pub fn new(program: &'a tx3_lang::ast::Program) -> Self {
+        let mut index = ProgramIndex {
+            symbols: Vec::new(),
+            occurrences: Vec::new(),
+            property_ops: Vec::new(),
+            struct_constructors: Vec::new(),
+            asset_constructors: Vec::new(),
+            literals: Vec::new(),
+            tx: None,
+            type_def: None,
+            in_symbols: true,
+        };
+
+        index.program(program);
+
+        index
+    }
+
+    fn push(&mut self, identifier: &'a tx3_lang::ast::Identifier, role: Role) {
+        // implicit names (eg: the default case of a record) have no source location
+        if identifier.span.start == identifier.span.end {
+            return;
+        }
+
+        self.occurrences.push(Occurrence {
+            identifier,
+            role,
+            tx: self.tx.map(|(_, tx)| tx),
+        });
+
+        if self.in_symbols {
+            self.symbols.push(SymbolContext {
+                identifier,
+                tx: self.tx,
+                type_def: self.type_def,
+            });
+        }
+    }
+
+    /// Pushes an occurrence that `find_symbol_in_program` can't resolve.
+    fn push_occurrence(&mut self, identifier: &'a tx3_lang::ast::Identifier, role: Role) {
+        let in_symbols = std::mem::replace(&mut self.in_symbols, false);
+        self.push(identifier, role);
+        self.in_symbols = in_symbols;
+    }
+
+    /// Walks an expression whose identifiers `find_symbol_in_program` skips.
+    fn hidden_data_expr(&mut self, expr: &'a tx3_lang::ast::DataExpr) {
+        let in_symbols = std::mem::replace(&mut self.in_symbols, false);
+        self.data_expr(expr);
+        self.in_symbols = in_symbols;
+    }
+
+    /// Address fields only resolve when they're a plain name.
+    fn address_expr(&mut self, expr: &'a tx3_lang::ast::DataExpr) {
+        match expr {
+            tx3_lang::ast::DataExpr::Identifier(_) => self.data_expr(expr),
+            _ => self.hidden_data_expr(expr),
+        }
+    }
+
+    fn program(&mut self, program: &'a tx3_lang::ast::Program) {
+        for party in &program.parties {
+            match party.span.start != party.span.end {
+                true => self.push(&party.name, Role::Declaration),
+                false => self.push_occurrence(&party.name, Role::Declaration),
+            }
+        }
+        for policy in &program.policies {
+            self.policy_def(policy);
+        }
+        for ty in &program.types {
+            self.type_def = Some(ty);
+            self.type_def(ty);
+            self.type_def = None;
+        }
+        for asset in &program.assets {
+            self.push_occurrence(&asset.name, Role::Declaration);
+            self.data_expr(&asset.policy);
+            self.data_expr(&asset.asset_name);
+        }
+        for (index, tx) in program.txs.iter().enumerate() {
+            self.tx = Some((index, tx));
+            self.tx_def(tx);
+            self.tx = None;
+        }
+    }
+
+    fn policy_def(&mut self, policy: &'a tx3_lang::ast::PolicyDef) {
+        let resolvable = matches!(policy.value, tx3_lang::ast::PolicyValue::Assign(_))
+            && policy.span.start != policy.span.end;
+
+        match resolvable {
+            true => self.push(&policy.name, Role::Declaration),
+            false => self.push_occurrence(&policy.name, Role::Declaration),
+        }
+
+        if let tx3_lang::ast::PolicyValue::Assign(hash) = &policy.value {
+            self.literals.push(Literal {
+                span: &hash.span,
+                kind: LiteralKind::Bytes,
+            });
+        }
+
+        if let tx3_lang::ast::PolicyValue::Constructor(constr) = &policy.value {
+            for field in &constr.fields {
+                match field {
+                    tx3_lang::ast::PolicyField::Hash(expr)
+                    | tx3_lang::ast::PolicyField::Script(expr)
+                    | tx3_lang::ast::PolicyField::Ref(expr) => self.data_expr(expr),
+                }
+            }
+        }
+    }
+
+    fn type_def(&mut self, ty: &'a tx3_lang::ast::TypeDef) {
+        self.push(&ty.name, Role::Declaration);
+
+        for case in &ty.cases {
+            self.push_occurrence(&case.name, Role::Member);
+            for field in &case.fields {
+                self.push(&field.name, Role::Member);
+                self.r#type(&field.r#type);
+            }
+        }
+    }
+
+    fn r#type(&mut self, ty: &'a tx3_lang::ast::Type) {
+        match ty {
+            tx3_lang::ast::Type::Custom(id) => self.push(id, Role::Reference),
+            tx3_lang::ast::Type::List(inner) => self.r#type(inner),
+            _ => {}
+        }
+    }
+
+    fn tx_def(&mut self, tx: &'a tx3_lang::ast::TxDef) {
+        self.push(&tx.name, Role::Declaration);
+
+        for param in &tx.parameters.parameters {
+            self.push(&param.name, Role::Declaration);
+            self.r#type(&param.r#type);
+        }
+
+        for reference in &tx.references {
+            self.data_expr(&reference.r#ref);
+        }
+
+        for input in &tx.inputs {
+            for field in &input.fields {
+                match field {
+                    tx3_lang::ast::InputBlockField::DatumIs(ty) => self.r#type(ty),
+                    tx3_lang::ast::InputBlockField::From(expr) => self.address_expr(expr),
+                    tx3_lang::ast::InputBlockField::MinAmount(expr)
+                    | tx3_lang::ast::InputBlockField::Redeemer(expr)
+                    | tx3_lang::ast::InputBlockField::Ref(expr) => self.data_expr(expr),
+                }
+            }
+        }
+
+        for col in &tx.collateral {
+            for field in &col.fields {
+                match field {
+                    tx3_lang::ast::CollateralBlockField::From(expr) => self.address_expr(expr),
+                    tx3_lang::ast::CollateralBlockField::MinAmount(expr)
+                    | tx3_lang::ast::CollateralBlockField::Ref(expr) => self.data_expr(expr),
+                }
+            }
+        }
+
+        for mint in tx.mints.iter().chain(&tx.burns) {
+            for field in &mint.fields {
+                match field {
+                    tx3_lang::ast::MintBlockField::Amount(expr)
+                    | tx3_lang::ast::MintBlockField::Redeemer(expr) => self.data_expr(expr),
+                }
+            }
+        }
+
+        for output in &tx.outputs {
+            if let Some(name) = &output.name {
+                self.push_occurrence(name, Role::Declaration);
+            }
+            for field in &output.fields {
+                match field {
+                    tx3_lang::ast::OutputBlockField::To(expr) => self.address_expr(expr),
+                    tx3_lang::ast::OutputBlockField::Amount(expr)
+                    | tx3_lang::ast::OutputBlockField::Datum(expr) => self.data_expr(expr),
+                }
+            }
+        }
+
+        if let Some(signers) = &tx.signers {
+            for signer in &signers.signers {
+                self.data_expr(signer);
+            }
+        }
+
+        if let Some(validity) = &tx.validity {
+            for field in &validity.fields {
+                match field {
+                    tx3_lang::ast::ValidityBlockField::SinceSlot(expr)
+                    | tx3_lang::ast::ValidityBlockField::UntilSlot(expr) => self.data_expr(expr),
+                }
+            }
+        }
+    }
+
+    fn data_expr(&mut self, expr: &'a tx3_lang::ast::DataExpr) {
+        match expr {
+            tx3_lang::ast::DataExpr::Identifier(id) => self.push(id, Role::Reference),
+            tx3_lang::ast::DataExpr::MinUtxo(id) => self.push_occurrence(id, Role::Reference),
+            tx3_lang::ast::DataExpr::StructConstructor(sc) => {
+                self.struct_constructors.push(sc);
+                self.push(&sc.r#type, Role::Reference);
+                self.push(&sc.case.name, Role::Member);
+                for field in &sc.case.fields {
+                    self.push(&field.name, Role::Member);
+                    self.data_expr(&field.value);
+                }
+                if let Some(spread) = &sc.case.spread {
+                    self.data_expr(spread);
+                }
+            }
+            tx3_lang::ast::DataExpr::ListConstructor(lc) => {
+                for el in &lc.elements {
+                    self.data_expr(el);
+                }
+            }
+            tx3_lang::ast::DataExpr::FnCall(x) => {
+                self.asset_constructors.push(AssetConstructor::Call(x));
+                self.push(&x.callee, Role::Reference);
+                for arg in &x.args {
+                    self.data_expr(arg);
+                }
+            }
+            tx3_lang::ast::DataExpr::AnyAssetConstructor(x) => {
+                self.asset_constructors.push(AssetConstructor::Any(x));
+                self.data_expr(&x.policy);
+                self.data_expr(&x.asset_name);
+                self.data_expr(&x.amount);
+            }
+            tx3_lang::ast::DataExpr::AddOp(x) => {
+                self.data_expr(&x.lhs);
+                self.data_expr(&x.rhs);
+            }
+            tx3_lang::ast::DataExpr::SubOp(x) => {
+                self.data_expr(&x.lhs);
+                self.data_expr(&x.rhs);
+            }
+            tx3_lang::ast::DataExpr::NegateOp(x) => self.data_expr(&x.operand),
+            tx3_lang::ast::DataExpr::PropertyOp(x) => {
+                if let Some((_, tx)) = self.tx {
+                    self.property_ops.push((x, tx));
+                }
+                self.data_expr(&x.operand);
+                match x.property.as_ref() {
+                    tx3_lang::ast::DataExpr::Identifier(id) => {
+                        self.push_occurrence(id, Role::Member)
+                    }
+                    other => self.hidden_data_expr(other),
+                }
+            }
+            tx3_lang::ast::DataExpr::String(x) => self.literals.push(Literal {
+                span: &x.span,
+                kind: LiteralKind::String,
+            }),
+            tx3_lang::ast::DataExpr::HexString(x) => self.literals.push(Literal {
+                span: &x.span,
+                kind: LiteralKind::Bytes,
+            }),
+            tx3_lang::ast::DataExpr::UtxoRef(x) => self.literals.push(Literal {
+                span: &x.span,
+                kind: LiteralKind::Bytes,
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_program(txs: usize) -> String {
+        let mut text = String::from(
+            "party Sender;\nparty Receiver;\n\ntype Order {\n    amount: Int,\n    owner: Bytes,\n}\n\n",
+        );
+
+        for i in 0..txs {
+            text.push_str(&format!(
+                r#"tx transfer_{i}(quantity: Int, order: Order) {{
+    input source {{
+        from: Sender,
+        min_amount: Ada(quantity),
+    }}
+    output {{
+        to: Receiver,
+        amount: Ada(quantity),
+        datum: Order {{ amount: quantity, owner: "abc" }},
+    }}
+    output {{
+        to: Sender,
+        amount: source - Ada(quantity),
+    }}
+}}
+
+"#
+            ));
+        }
+
+        text
+    }
+
+    #[test]
+    fn symbols_match_what_find_symbol_resolves() {
+        let text = generate_program(2);
+        let ast = tx3_lang::parsing::parse_string(&text).unwrap();
+        let index = ProgramIndex::new(&ast);
+
+        let spans: Vec<_> = index
+            .symbols
+            .iter()
+            .map(|x| (x.identifier.span.start, x.identifier.span.end))
+            .collect();
+
+        for offset in 0..text.len() {
+            let found = match find_symbol_in_program(&ast, offset) {
+                Some(SymbolAtOffset::Identifier(x)) => x,
+                Some(SymbolAtOffset::TypeIdentifier(tx3_lang::ast::Type::Custom(x))) => x,
+                _ => continue,
+            };
+
+            assert!(
+                spans.contains(&(found.span.start, found.span.end)),
+                "`{}` at {} is missing from the symbols",
+                found.value,
+                offset
+            );
+        }
+
+        for symbol in &index.symbols {
+            assert!(
+                find_symbol_in_program(&ast, symbol.identifier.span.start).is_some(),
+                "`{}` can't be resolved",
+                symbol.identifier.value
+            );
+        }
+    }
+
+    fn spans<'a>(
+        identifiers: impl Iterator<Item = &'a tx3_lang::ast::Identifier>,
+    ) -> Vec<(usize, usize)> {
+        identifiers.map(|x| (x.span.start, x.span.end)).collect()
+    }
+
+    #[test]
+    fn the_index_matches_the_per_request_collectors() {
+        let text = generate_program(50);
+        let ast = tx3_lang::parsing::parse_string(&text).unwrap();
+        let index = ProgramIndex::new(&ast);
+
+        let occurrences = collect_occurrences(&ast);
+        assert_eq!(
+            spans(index.occurrences.iter().map(|x| x.identifier)),
+            spans(occurrences.iter().map(|x| x.identifier))
+        );
+        assert_eq!(
+            index.occurrences.iter().map(|x| x.role).collect::<Vec<_>>(),
+            occurrences.iter().map(|x| x.role).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            index
+                .literals
+                .iter()
+                .map(|x| (x.span.start, x.span.end, x.kind))
+                .collect::<Vec<_>>(),
+            collect_literals(&ast)
+                .iter()
+                .map(|x| (x.span.start, x.span.end, x.kind))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            index.struct_constructors.len(),
+            collect_struct_constructors(&ast).len()
+        );
+        assert_eq!(
+            index.asset_constructors.len(),
+            collect_asset_constructors(&ast).len()
+        );
+    }
+}
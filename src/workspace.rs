@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::Url;
+use tx3_lang::ast::Program;
+
+use crate::Context;
+
+/// A `.tx3` file found on disk, kept parsed so that workspace-wide lookups
+/// work before it's opened.
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub rope: Rope,
+    pub ast: Arc<Program>,
+}
+
+pub fn is_tx3_file(uri: &Url) -> bool {
+    uri.path().ends_with(".tx3")
+}
+
+/// Reads and parses the file behind `uri`. Files that can't be read or
+/// don't parse aren't worth indexing.
+async fn load_file(uri: &Url) -> Option<IndexedFile> {
+    let path = uri.to_file_path().ok()?;
+    let text = tokio::fs::read_to_string(path).await.ok()?;
+    let ast = tx3_lang::parsing::parse_string(&text).ok()?;
+
+    Some(IndexedFile {
+        rope: Rope::from_str(&text),
+        ast: Arc::new(ast),
+    })
+}
+
+/// Lists the `.tx3` files under `root`, skipping hidden directories (eg:
+/// `.git`) and build outputs.
+async fn find_files(root: PathBuf) -> Vec<Url> {
+    let mut files = Vec::new();
+    let mut pending = vec![root];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                    pending.push(path);
+                }
+            } else if name.ends_with(".tx3") {
+                files.extend(Url::from_file_path(path).ok());
+            }
+        }
+    }
+
+    files
+}
+
+impl Context {
+    /// Re-reads a file into the workspace index, dropping it if it's gone or
+    /// no longer parses.
+    pub async fn index_file(&self, uri: &Url) {
+        match load_file(uri).await {
+            Some(file) => {
+                self.workspace.insert(uri.clone(), file);
+            }
+            None => {
+                self.workspace.remove(uri);
+            }
+        }
+    }
+
+    pub fn unindex_file(&self, uri: &Url) {
+        self.workspace.remove(uri);
+    }
+
+    /// Indexes every `.tx3` file under the workspace folders.
+    pub async fn index_workspace(&self) {
+        let roots = self.workspace_roots.read().unwrap().clone();
+
+        for root in roots {
+            let Ok(path) = root.to_file_path() else {
+                continue;
+            };
+
+            for uri in find_files(path).await {
+                self.index_file(&uri).await;
+            }
+        }
+    }
+
+    /// The parsed files visible to workspace-wide lookups: the open documents,
+    /// plus the indexed files that aren't open.
+    pub fn workspace_files(&self) -> Vec<(Url, IndexedFile)> {
+        // snapshot the maps so no guard is held while parsing
+        let open: Vec<(Url, Rope)> = self
+            .documents
+            .iter()
+            .map(|x| (x.key().clone(), x.value().clone()))
+            .collect();
+
+        let mut files: Vec<(Url, IndexedFile)> = self
+            .workspace
+            .iter()
+            .filter(|x| !open.iter().any(|(uri, _)| uri == x.key()))
+            .map(|x| (x.key().clone(), x.value().clone()))
+            .collect();
+
+        for (uri, rope) in open {
+            if let Some(ast) = self.get_document_ast(&uri, &rope) {
+                files.push((uri, IndexedFile { rope, ast }));
+            }
+        }
+
+        files.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+        files
+    }
+}